@@ -159,6 +159,12 @@ pub struct Attribute {
     pub tags: Vec<(String, Vec<Argument>)>,
 }
 
+impl Default for Attribute {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Attribute {
     pub fn new() -> Self {
         Attribute { tags: Vec::new() }
@@ -216,13 +222,6 @@ pub enum Path {
     Decrement,
 }
 
-#[derive(Clone, PartialEq, Debug)]
-pub struct Definition {
-    pub symbol: String,
-    pub value: Expression,
-    //pub mutable: bool,
-}
-
 #[derive(Clone, PartialEq, Debug)]
 pub struct Argument {
     pub symbol: Option<String>,
@@ -293,12 +292,6 @@ pub struct Case {
     pub body: Expression,
 }
 
-#[derive(Clone, PartialEq, Debug)]
-pub struct Switch {
-    pub value: Expression,
-    pub cases: Vec<Case>,
-}
-
 #[derive(Clone, PartialEq, Debug)]
 pub struct Error {
     pub message: Expression,
@@ -5,6 +5,7 @@ use crate::compiler_types::*;
 use crate::context::*;
 use crate::globals::Globals;
 use crate::levelstring::*;
+use crate::target::{parse_role, TriggerInfo, TriggerRole};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
@@ -29,13 +30,13 @@ macro_rules! arg_length {
 
 pub type ArbitraryId = u16;
 pub type SpecificId = u16;
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Id {
     Specific(SpecificId),
     Arbitrary(ArbitraryId), // will be given specific ids at the end of compilation
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Group {
     pub id: Id,
 }
@@ -79,7 +80,7 @@ impl Color {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Block {
     pub id: Id,
 }
@@ -100,7 +101,7 @@ impl Block {
         }
     }
 }
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Item {
     pub id: Id,
 }
@@ -122,7 +123,7 @@ impl Item {
     }
 }
 
-pub fn context_trigger(context: &Context, uid_counter: &mut usize) -> GdObj {
+pub fn context_trigger(context: &Context, uid_counter: &mut usize, info: &CompilerInfo) -> GdObj {
     let mut params = HashMap::new();
     params.insert(57, ObjParam::Group(context.start_group));
     (*uid_counter) += 1;
@@ -133,6 +134,7 @@ pub fn context_trigger(context: &Context, uid_counter: &mut usize) -> GdObj {
         unique_id: *uid_counter,
         sync_group: context.sync_group,
         sync_part: context.sync_part,
+        source: Some(ObjSource::from_info(info)),
     }
 }
 
@@ -145,10 +147,7 @@ impl Value {
         globals: &mut Globals,
     ) -> Option<StoredValue> {
         let get_impl = |t: u16, m: String| match globals.implementations.get(&t) {
-            Some(imp) => match imp.get(&m) {
-                Some(mem) => Some(mem.0),
-                None => None,
-            },
+            Some(imp) => imp.get(&m).map(|mem| mem.0),
             None => None,
         };
         if member == TYPE_MEMBER_NAME {
@@ -182,25 +181,21 @@ impl Value {
                 //         ));
                 //     }
                 // }
-                Value::Str(a) => {
-                    if member == "length" {
-                        return Some(store_value(
-                            Value::Number(a.len() as f64),
-                            1,
-                            globals,
-                            context,
-                        ));
-                    }
+                Value::Str(a) if member == "length" => {
+                    return Some(store_value(
+                        Value::Number(a.len() as f64),
+                        1,
+                        globals,
+                        context,
+                    ));
                 }
-                Value::Array(a) => {
-                    if member == "length" {
-                        return Some(store_const_value(
-                            Value::Number(a.len() as f64),
-                            1,
-                            globals,
-                            context,
-                        ));
-                    }
+                Value::Array(a) if member == "length" => {
+                    return Some(store_const_value(
+                        Value::Number(a.len() as f64),
+                        1,
+                        globals,
+                        context,
+                    ));
                 }
                 Value::Range(start, end, step) => match member.as_ref() {
                     "start" => {
@@ -268,6 +263,96 @@ impl Value {
     }
 }
 
+// used by $.level_settings and the level setting file attributes
+pub fn set_level_setting(
+    key: &str,
+    val: &Value,
+    settings: &mut LevelSettings,
+) -> Result<(), String> {
+    let number = |max: f64| match val {
+        Value::Number(n) if *n >= 0.0 && *n <= max && n.fract() == 0.0 => Ok(*n),
+        _ => Err(format!(
+            "Expected a whole number between 0 and {} for the level setting {}",
+            max, key
+        )),
+    };
+    let boolean = || match val {
+        Value::Bool(b) => Ok(*b),
+        _ => Err(format!("Expected a boolean for the level setting {}", key)),
+    };
+
+    match key {
+        "name" | "description" => {
+            let s = match val {
+                Value::Str(s) => s.clone(),
+                _ => return Err(format!("Expected a string for the level setting {}", key)),
+            };
+            if key == "name" {
+                if s.is_empty() {
+                    return Err("The level name can not be empty".to_string());
+                }
+                settings.name = Some(s);
+            } else {
+                settings.description = Some(s);
+            }
+        }
+        "song" => settings.song = Some(number(u32::MAX as f64)? as u32),
+        "custom_song" => settings.custom_song = Some(number(u32::MAX as f64)? as u32),
+        "game_mode" => settings.game_mode = Some(number(6.0)? as u8),
+        "speed" => settings.speed = Some(number(4.0)? as u8),
+        "mini" => settings.mini = Some(boolean()?),
+        "dual" => settings.dual = Some(boolean()?),
+        a => {
+            return Err(format!(
+                "Unknown level setting {} (expected name, description, song, custom_song, game_mode, speed, mini or dual)",
+                a
+            ))
+        }
+    };
+    Ok(())
+}
+
+// used by $.level_color and the level_color file attribute
+pub fn set_level_color(args: &[Value], settings: &mut LevelSettings) -> Result<(), String> {
+    let id = match args.first() {
+        Some(Value::Color(Color {
+            id: Id::Specific(id),
+        })) => *id,
+        Some(Value::Color(_)) => {
+            return Err("Level colors need a specific color ID (like 1c)".to_string())
+        }
+        _ => return Err("Expected a color as the first argument".to_string()),
+    };
+
+    let mut rgb = [0u8; 3];
+    for (i, channel) in rgb.iter_mut().enumerate() {
+        *channel = match args.get(i + 1) {
+            Some(Value::Number(n)) if *n >= 0.0 && *n <= 255.0 => *n as u8,
+            _ => return Err("Expected red, green and blue values between 0 and 255".to_string()),
+        };
+    }
+
+    let blending = match args.get(4) {
+        Some(Value::Bool(b)) => *b,
+        None => false,
+        _ => return Err("Expected a boolean for blending".to_string()),
+    };
+    let opacity = match args.get(5) {
+        Some(Value::Number(n)) if *n >= 0.0 && *n <= 1.0 => *n,
+        None => 1.0,
+        _ => return Err("Expected an opacity between 0 and 1".to_string()),
+    };
+
+    settings.colors.retain(|c| c.id != id);
+    settings.colors.push(ColorChannel {
+        id,
+        rgb: (rgb[0], rgb[1], rgb[2]),
+        blending,
+        opacity,
+    });
+    Ok(())
+}
+
 pub const BUILTIN_LIST: &[&str] = &[
     "assert",
     "print",
@@ -296,6 +381,10 @@ pub const BUILTIN_LIST: &[&str] = &[
     "substr",
     "remove_index",
     "regex",
+    "level_objects",
+    "level_settings",
+    "level_color",
+    "register_trigger",
     //operators
     "_or_",
     "_and_",
@@ -433,7 +522,7 @@ pub fn built_in_function(
             let val = globals.stored_values[arguments[0]].clone();
             match val {
                 Value::Str(s) => {
-                    let encrypted = base64::encode(&s.as_bytes());
+                    let encrypted = base64::encode(s.as_bytes());
                     Value::Str(encrypted)
                 }
                 _ => {
@@ -537,7 +626,7 @@ pub fn built_in_function(
             match &globals.stored_values[arguments[0]] {
                 // if its an object
                 Value::Obj(obj, mode) => {
-                    let c_t = context_trigger(context, &mut globals.uid_counter); // i dont know
+                    let c_t = context_trigger(context, &mut globals.uid_counter, &info); // i dont know
 
                     let mut obj_map = HashMap::<u16, ObjParam>::new();
 
@@ -554,7 +643,7 @@ pub fn built_in_function(
                                     info
                                 });
                             }
-                            (*globals).uid_counter += 1;
+                            globals.uid_counter += 1;
                             let obj = GdObj {
                                 params: obj_map,
                                 func_id: context.func_id,
@@ -562,8 +651,9 @@ pub fn built_in_function(
                                 unique_id: globals.uid_counter,
                                 sync_group: context.sync_group,
                                 sync_part: context.sync_part,
+                                source: Some(ObjSource::from_info(&info)),
                             };
-                            (*globals).objects.push(obj)
+                            globals.objects.push(obj)
                         }
                         ObjectMode::Trigger => {
                             let obj = GdObj {
@@ -572,8 +662,8 @@ pub fn built_in_function(
                                 ..c_t
                             }
                             .context_parameters(context);
-                            (*globals).trigger_order += 1;
-                            (*globals).func_ids[context.func_id]
+                            globals.trigger_order += 1;
+                            globals.func_ids[context.func_id]
                                 .obj_list
                                 .push((obj, globals.trigger_order))
                         }
@@ -713,7 +803,7 @@ pub fn built_in_function(
                         Value::Dict(d) => {
                             // this is specifically for object_key dicts
                             let gotten_type = d.get(TYPE_MEMBER_NAME);
-                            if gotten_type == None
+                            if gotten_type.is_none()
                                 || globals.stored_values[*gotten_type.unwrap()]
                                     != Value::TypeIndicator(19)
                             {
@@ -725,7 +815,7 @@ pub fn built_in_function(
                                 });
                             }
                             let id = d.get("id");
-                            if id == None {
+                            if id.is_none() {
                                 return Err(RuntimeError::RuntimeError {
                                     // object_key has an ID member for the key basically
                                     message: "object key has no 'id' member".to_string(),
@@ -733,7 +823,7 @@ pub fn built_in_function(
                                 });
                             }
                             let pattern = d.get("pattern");
-                            if pattern == None {
+                            if pattern.is_none() {
                                 return Err(RuntimeError::RuntimeError {
                                     // same with pattern, for the expected type
                                     message: "object key has no 'pattern' member".to_string(),
@@ -781,7 +871,7 @@ pub fn built_in_function(
                     };
 
                     if let Some(ref pat) = pattern {
-                        if !value.matches_pat(&pat, &info, globals, &context)? {
+                        if !value.matches_pat(pat, &info, globals, context)? {
                             return Err(RuntimeError::RuntimeError {
                                 message: format!(
                                     "key required value to match {}, found {}",
@@ -1181,6 +1271,248 @@ pub fn built_in_function(
             }
         }
 
+        "level_objects" => {
+            if arguments.len() > 1 {
+                return Err(RuntimeError::BuiltinError {
+                    message:
+                        "Expected no arguments, or a dictionary of filters (obj_id, group, x, y)"
+                            .to_string(),
+                    info,
+                });
+            }
+
+            let mut obj_id = None;
+            let mut group = None;
+            let mut x_range = None;
+            let mut y_range = None;
+
+            if let Some(filter) = arguments.first() {
+                let filter = match globals.stored_values[*filter].clone() {
+                    Value::Dict(d) => d,
+                    a => {
+                        return Err(RuntimeError::BuiltinError {
+                            message: format!("Expected dictionary, found {}", a.to_str(globals)),
+                            info,
+                        })
+                    }
+                };
+                for (key, val) in filter {
+                    match (key.as_str(), &globals.stored_values[val]) {
+                        ("obj_id", Value::Number(n)) => obj_id = Some(*n),
+                        ("group", Value::Group(g)) => group = Some(*g),
+                        ("x", Value::Range(start, end, _)) => x_range = Some((*start, *end)),
+                        ("y", Value::Range(start, end, _)) => y_range = Some((*start, *end)),
+                        (k, a) => {
+                            return Err(RuntimeError::BuiltinError {
+                                message: format!(
+                                    "Invalid filter {}: {} (expected obj_id: @number, group: @group, x: @range or y: @range)",
+                                    k,
+                                    a.to_str(globals)
+                                ),
+                                info,
+                            })
+                        }
+                    }
+                }
+            }
+
+            let in_range = |val: Option<&ObjParam>, range: Option<(i32, i32)>| match range {
+                Some((start, end)) => match val {
+                    Some(ObjParam::Number(n)) => *n >= start as f64 && *n < end as f64,
+                    _ => false,
+                },
+                None => true,
+            };
+
+            let mut out = Vec::new();
+            for obj in globals.level_objects.clone() {
+                if let Some(id) = obj_id {
+                    if obj.params.get(&1) != Some(&ObjParam::Number(id)) {
+                        continue;
+                    }
+                }
+                if let Some(g) = group {
+                    if !obj.has_group(g) {
+                        continue;
+                    }
+                }
+                if !in_range(obj.params.get(&2), x_range) || !in_range(obj.params.get(&3), y_range)
+                {
+                    continue;
+                }
+
+                let mut params = obj.params.into_iter().collect::<Vec<(u16, ObjParam)>>();
+                params.sort_by_key(|p| p.0);
+                out.push(store_const_value(
+                    Value::Obj(params, obj.mode),
+                    1,
+                    globals,
+                    context,
+                ));
+            }
+            Value::Array(out)
+        }
+
+        "level_settings" => {
+            arg_length!(
+                info,
+                1,
+                arguments,
+                "Expected one argument: a dictionary of level settings".to_string()
+            );
+
+            let settings = match globals.stored_values[arguments[0]].clone() {
+                Value::Dict(d) => d,
+                a => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: format!("Expected dictionary, found {}", a.to_str(globals)),
+                        info,
+                    })
+                }
+            };
+            let mut settings = settings.into_iter().collect::<Vec<(String, usize)>>();
+            settings.sort_by(|a, b| a.0.cmp(&b.0));
+
+            for (key, val) in settings {
+                let val = globals.stored_values[val].clone();
+                if let Err(message) = set_level_setting(&key, &val, &mut globals.level_settings) {
+                    return Err(RuntimeError::BuiltinError { message, info });
+                }
+            }
+            Value::Null
+        }
+
+        "level_color" => {
+            if arguments.len() < 4 || arguments.len() > 6 {
+                return Err(RuntimeError::BuiltinError {
+                    message:
+                        "Expected 4 to 6 arguments: channel, red, green, blue, [blending, opacity]"
+                            .to_string(),
+                    info,
+                });
+            }
+            let args = arguments
+                .iter()
+                .map(|a| globals.stored_values[*a].clone())
+                .collect::<Vec<Value>>();
+            match set_level_color(&args, &mut globals.level_settings) {
+                Ok(()) => Value::Null,
+                Err(message) => return Err(RuntimeError::BuiltinError { message, info }),
+            }
+        }
+
+        "register_trigger" => {
+            arg_length!(
+                info,
+                2,
+                arguments,
+                "Expected two arguments: the object ID and a dictionary with the trigger's role and keys"
+                    .to_string()
+            );
+
+            // object ids and keys are whole numbers that fit in a u16
+            let to_u16 = |n: f64| {
+                if n.fract() == 0.0 && n >= 0.0 && n <= u16::MAX as f64 {
+                    Some(n as u16)
+                } else {
+                    None
+                }
+            };
+
+            let obj_id = match globals.stored_values[arguments[0]] {
+                Value::Number(n) if to_u16(n).is_some() => n as u16,
+                _ => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: "Expected an object ID as the first argument".to_string(),
+                        info,
+                    })
+                }
+            };
+            let dict = match globals.stored_values[arguments[1]].clone() {
+                Value::Dict(d) => d,
+                a => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: format!("Expected dictionary, found {}", a.to_str(globals)),
+                        info,
+                    })
+                }
+            };
+
+            let mut trigger_info = TriggerInfo {
+                role: TriggerRole::Output,
+                groups: Vec::new(),
+                item_reads: Vec::new(),
+                item_writes: Vec::new(),
+                block_reads: Vec::new(),
+            };
+            for (key, val) in dict {
+                let val = globals.stored_values[val].clone();
+                let list = match key.as_str() {
+                    "role" => {
+                        trigger_info.role = match &val {
+                            Value::Str(s) => match parse_role(s) {
+                                Ok(r) => r,
+                                Err(message) => {
+                                    return Err(RuntimeError::BuiltinError { message, info })
+                                }
+                            },
+                            _ => {
+                                return Err(RuntimeError::BuiltinError {
+                                    message: "Expected a string for role".to_string(),
+                                    info,
+                                })
+                            }
+                        };
+                        continue;
+                    }
+                    "groups" => &mut trigger_info.groups,
+                    "item_reads" => &mut trigger_info.item_reads,
+                    "item_writes" => &mut trigger_info.item_writes,
+                    "block_reads" => &mut trigger_info.block_reads,
+                    a => {
+                        return Err(RuntimeError::BuiltinError {
+                            message: format!(
+                                "Unknown key {} (expected role, groups, item_reads, item_writes or block_reads)",
+                                a
+                            ),
+                            info,
+                        })
+                    }
+                };
+                let keys = match val {
+                    Value::Array(a) => a,
+                    _ => {
+                        return Err(RuntimeError::BuiltinError {
+                            message: format!("Expected an array of keys for {}", key),
+                            info,
+                        })
+                    }
+                };
+                for k in keys {
+                    let n = match &globals.stored_values[k] {
+                        Value::Number(n) => Some(*n),
+                        // obj_props entries like obj_props.TARGET
+                        Value::Dict(d) => match d.get("id").map(|id| &globals.stored_values[*id]) {
+                            Some(Value::Number(n)) => Some(*n),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    match n.and_then(to_u16) {
+                        Some(k) => list.push(k),
+                        None => {
+                            return Err(RuntimeError::BuiltinError {
+                                message: format!("Expected object keys in {}", key),
+                                info,
+                            })
+                        }
+                    }
+                }
+            }
+            globals.trigger_table.insert(obj_id, trigger_info);
+            Value::Null
+        }
+
         "_or_" | "_and_" | "_more_than_" | "_less_than_" | "_more_or_equal_"
         | "_less_or_equal_" | "_divided_by_" | "_intdivided_by_" | "_times_" | "_mod_"
         | "_pow_" | "_plus_" | "_minus_" | "_equal_" | "_not_equal_" | "_assign_" | "_swap_"
@@ -1508,7 +1840,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                     (Value::Obj(o, _m), Value::Dict(d)) => {
                         let gotten_type = d.get(TYPE_MEMBER_NAME);
 
-                        if gotten_type == None
+                        if gotten_type.is_none()
                             || globals.stored_values[*gotten_type.unwrap()]
                                 != Value::TypeIndicator(19)
                         {
@@ -1521,7 +1853,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                         }
 
                         let id = d.get("id");
-                        if id == None {
+                        if id.is_none() {
                             return Err(RuntimeError::BuiltinError {
                                 // object_key has an ID member for the key basically
                                 message: "object key has no 'id' member".to_string(),
@@ -1582,7 +1914,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                         t,
                         &info,
                         globals,
-                        &context,
+                        context,
                     )?,
 
                     _ => {
@@ -1598,7 +1930,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                         18,
                         &info,
                         globals,
-                        &context,
+                        context,
                     )? {
                         Box::new(p)
                     } else {
@@ -1609,7 +1941,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                         18,
                         &info,
                         globals,
-                        &context,
+                        context,
                     )? {
                         Box::new(p)
                     } else {
@@ -1801,3 +2133,122 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    // a block at the given position, in the given group
+    fn block(x: f64, y: f64, in_group: u16) -> GdObj {
+        obj(
+            ObjectMode::Object,
+            vec![
+                (1, ObjParam::Number(1.0)),
+                (2, ObjParam::Number(x)),
+                (3, ObjParam::Number(y)),
+                (57, specific(group, in_group)),
+            ],
+        )
+    }
+
+    #[test]
+    fn level_objects() {
+        let level = vec![
+            block(15.0, 15.0, 5),
+            block(45.0, 15.0, 5),
+            block(75.0, 315.0, 6),
+        ];
+        let positions = |filter: &str| {
+            let globals = compile(
+                &format!("for o in $.level_objects({}) {{ $.add(o) }}", filter),
+                level.clone(),
+            )
+            .unwrap();
+            globals
+                .objects
+                .iter()
+                .map(|o| (o.params[&2].to_string(), o.params[&3].to_string()))
+                .collect::<Vec<(String, String)>>()
+        };
+        let pos = |x: &str, y: &str| (x.to_string(), y.to_string());
+
+        assert_eq!(positions("").len(), 3);
+        assert_eq!(
+            positions("{group: 5g}"),
+            vec![pos("15", "15"), pos("45", "15")]
+        );
+        assert_eq!(
+            positions("{x: 30..100}"),
+            vec![pos("45", "15"), pos("75", "315")]
+        );
+        assert_eq!(positions("{group: 5g, x: 30..100}"), vec![pos("45", "15")]);
+        assert_eq!(positions("{y: 0..30, obj_id: 1}").len(), 2);
+        assert!(positions("{obj_id: 2}").is_empty());
+
+        assert!(compile("$.level_objects({size: 5})", level.clone()).is_err());
+        assert!(compile("$.level_objects(5)", level).is_err());
+    }
+
+    #[test]
+    fn level_settings() {
+        let settings = |code: &str| compile(code, Vec::new()).map(|g| g.level_settings);
+
+        let s = settings(
+            "$.level_settings({name: \"test\", game_mode: 1, mini: true})\n$.level_color(1000c, 40, 125, 255)",
+        )
+        .unwrap();
+        assert_eq!(s.name, Some("test".to_string()));
+        assert_eq!(s.game_mode, Some(1));
+        assert_eq!(s.mini, Some(true));
+        assert_eq!(
+            s.colors,
+            vec![ColorChannel {
+                id: 1000,
+                rgb: (40, 125, 255),
+                blending: false,
+                opacity: 1.0
+            }]
+        );
+
+        // the same settings as file attributes
+        let s =
+            settings("#[game_mode(2) speed(3) level_color(1c, 0, 0, 0, true, 0.5)]\n1").unwrap();
+        assert_eq!(s.game_mode, Some(2));
+        assert_eq!(s.speed, Some(3));
+        assert!(s.colors[0].blending);
+        assert_eq!(s.colors[0].opacity, 0.5);
+
+        assert!(settings("$.level_settings({game_mode: 7})").is_err());
+        assert!(settings("$.level_settings({mini: 1})").is_err());
+        assert!(settings("$.level_settings({name: \"\"})").is_err());
+        assert!(settings("$.level_settings({size: 5})").is_err());
+        assert!(settings("$.level_color(1000c, 300, 0, 0)").is_err());
+        assert!(settings("$.level_color(?c, 0, 0, 0)").is_err());
+    }
+
+    #[test]
+    fn registered_triggers() {
+        let table = |code: &str| compile(code, Vec::new()).map(|g| g.trigger_table);
+
+        let t =
+            table("$.register_trigger(3600, {role: \"func\", groups: [51, 71], item_reads: [80]})")
+                .unwrap();
+        assert_eq!(
+            t[&3600],
+            TriggerInfo {
+                role: TriggerRole::Func,
+                groups: vec![51, 71],
+                item_reads: vec![80],
+                item_writes: Vec::new(),
+                block_reads: Vec::new(),
+            }
+        );
+        // unknown roles and keys, and keys that aren't whole numbers
+        assert!(table("$.register_trigger(3600, {role: \"teleport\"})").is_err());
+        assert!(table("$.register_trigger(3600, {targets: [51]})").is_err());
+        assert!(table("$.register_trigger(3600, {groups: [51.5]})").is_err());
+        assert!(table("$.register_trigger(-1, {})").is_err());
+        assert!(table("$.register_trigger(70000, {})").is_err());
+    }
+}
@@ -21,6 +21,7 @@ pub const CONTEXT_MAX: usize = 2;
 use termcolor::Color as TColor;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum RuntimeError {
     UndefinedErr {
         undefined: String,
@@ -62,19 +63,14 @@ pub fn print_error_intro(pos: crate::parser::FileRange, file: &Path) {
         write!(&mut stdout, "{}", text).unwrap();
     };
 
-    let path_str = format!(
-        "{}:{}:{}",
-        file.to_string_lossy().to_string(),
-        pos.0 .0,
-        pos.0 .1 + 1
-    );
+    let path_str = format!("{}:{}:{}", file.to_string_lossy(), pos.0 .0, pos.0 .1 + 1);
 
     write_with_color("Error", TColor::Red);
     write_with_color(&format!(" at {}\n", path_str), TColor::White);
 
     if pos.0 .0 == pos.1 .0 {
         use std::io::BufRead;
-        if let Ok(file) = fs::File::open(&file) {
+        if let Ok(file) = fs::File::open(file) {
             if let Some(Ok(line)) = std::io::BufReader::new(file).lines().nth(pos.0 .0 - 1) {
                 let line_num = pos.1 .0.to_string();
 
@@ -166,14 +162,32 @@ impl std::error::Error for RuntimeError {
 pub const NULL_STORAGE: usize = 1;
 pub const BUILTIN_STORAGE: usize = 0;
 
+// the namespace of a script's objects in the level: the namespace attribute,
+// or the name of the script file
+pub fn script_namespace(notes: &ParseNotes, path: &Path) -> Result<String, String> {
+    match notes.tag.get("namespace") {
+        Some(args) => match args.first().map(|a| &a.value.values[0].value.body) {
+            Some(ast::ValueBody::Str(s)) if args.len() == 1 && !s.is_empty() => Ok(s.clone()),
+            _ => Err("The namespace attribute takes one non-empty string".to_string()),
+        },
+        None => Ok(match path.file_stem() {
+            Some(stem) => stem.to_string_lossy().to_string(),
+            None => String::new(),
+        }),
+    }
+}
+
 pub fn compile_spwn(
     statements: Vec<ast::Statement>,
     path: PathBuf,
     included_paths: Vec<PathBuf>,
     notes: ParseNotes,
+    level_objects: Vec<GdObj>,
 ) -> Result<Globals, RuntimeError> {
     //variables that get changed throughout the compiling
     let mut globals = Globals::new(path.clone());
+    globals.level_objects = level_objects;
+    globals.func_ids[0].file = Some(path.clone());
     if statements.is_empty() {
         return Err(RuntimeError::RuntimeError {
             message: "this script is empty".to_string(),
@@ -184,6 +198,7 @@ pub fn compile_spwn(
                 current_file: path,
                 current_module: String::new(),
                 includes: vec![],
+                caller: None,
             },
         });
     }
@@ -199,6 +214,7 @@ pub fn compile_spwn(
         current_file: path,
         current_module: String::new(),
         includes: included_paths,
+        caller: None,
     };
     use std::time::Instant;
 
@@ -235,6 +251,46 @@ pub fn compile_spwn(
         }
     }
 
+    // level settings given as file attributes, like #[game_mode(1) song(5)]
+    for (name, args) in &notes.tag.tags {
+        let setting = match name.as_str() {
+            "level_name" => "name",
+            "level_description" => "description",
+            "song" | "custom_song" | "game_mode" | "speed" | "mini" | "dual" | "level_color" => {
+                name.as_str()
+            }
+            _ => continue,
+        };
+
+        let mut values = Vec::new();
+        for arg in args {
+            let (evaled, _) =
+                arg.value
+                    .eval(&start_context, &mut globals, start_info.clone(), true)?;
+            if evaled.len() != 1 {
+                return Err(RuntimeError::RuntimeError {
+                    message: format!("The {} attribute can not split the context", name),
+                    info: start_info,
+                });
+            }
+            values.push(globals.stored_values[evaled[0].0].clone());
+        }
+
+        let result = if setting == "level_color" {
+            set_level_color(&values, &mut globals.level_settings)
+        } else if values.len() == 1 {
+            set_level_setting(setting, &values[0], &mut globals.level_settings)
+        } else {
+            Err(format!("The {} attribute takes one argument", name))
+        };
+        if let Err(message) = result {
+            return Err(RuntimeError::RuntimeError {
+                message,
+                info: start_info,
+            });
+        }
+    }
+
     let (contexts, _) = compile_scope(
         &statements,
         smallvec![start_context],
@@ -302,7 +358,7 @@ pub fn compile_scope(
     let mut to_be_removed = SmallVec::<[usize; CONTEXT_MAX]>::new();
 
     for (i, c) in contexts.iter().enumerate() {
-        if c.broken != None {
+        if c.broken.is_some() {
             broken_contexts.push(c.clone());
             to_be_removed.push(i)
         }
@@ -351,7 +407,7 @@ pub fn compile_scope(
                 for context in &contexts {
                     let is_assign = !expr.operators.is_empty()
                         && expr.operators[0] == ast::Operator::Assign
-                        && !expr.values[0].is_undefinable(&context, globals);
+                        && !expr.values[0].is_undefinable(context, globals);
 
                     //println!("{:?}, {}", expr, is_assign);
 
@@ -361,6 +417,11 @@ pub fn compile_scope(
                         //use crate::fmt::SpwnFmt;
                         new_expr.operators.remove(0); //assign operator
                         let mutable = symbol.operator == Some(ast::UnaryOperator::Let);
+                        // for naming arbitrary IDs in the lockfile
+                        let symbol_name = match &symbol.value.body {
+                            ast::ValueBody::Symbol(s) if symbol.path.is_empty() => Some(s.clone()),
+                            _ => None,
+                        };
 
                         //let mut new_context = context.clone();
 
@@ -374,14 +435,19 @@ pub fn compile_scope(
                                 //to account for recursion
 
                                 //create the function context
-                                let mut new_context = context.clone();
+                                let mut new_context = context.next_fn_id(globals);
+                                globals.mark_fn_id(new_context.func_id, symbol_name.clone(), &info);
                                 let storage = symbol.define(&mut new_context, globals, &info)?;
 
                                 //pick a start group
                                 let start_group = Group::next_free(&mut globals.closed_groups);
+                                globals.add_id_origin(0, start_group.id, &info);
                                 //store value
                                 globals.stored_values[storage] =
                                     Value::TriggerFunc(TriggerFunction { start_group });
+                                if let Some(name) = &symbol_name {
+                                    globals.name_id_origin(storage, name);
+                                }
 
                                 new_context.start_group = start_group;
 
@@ -446,6 +512,9 @@ pub fn compile_scope(
 
                                     globals.stored_values[storage] =
                                         globals.stored_values[cloned].clone();
+                                    if let Some(name) = &symbol_name {
+                                        globals.name_id_origin(storage, name);
+                                    }
                                     new_contexts.push(new_context);
                                 }
                             }
@@ -525,8 +594,8 @@ pub fn compile_scope(
                         });
                     }
                 } else {
-                    (*globals).type_id_count += 1;
-                    (*globals).type_ids.insert(
+                    globals.type_id_count += 1;
+                    globals.type_ids.insert(
                         name.clone(),
                         (globals.type_id_count, info.current_file.clone(), info.pos.0),
                     );
@@ -717,13 +786,13 @@ pub fn compile_scope(
                         },
                     );
                     params.insert(1, ObjParam::Number(1268.0));
-                    (*globals).trigger_order += 1;
+                    globals.trigger_order += 1;
 
-                    (*globals).func_ids[context.func_id].obj_list.push((
+                    globals.func_ids[context.func_id].obj_list.push((
                         GdObj {
                             params,
 
-                            ..context_trigger(&context, &mut globals.uid_counter)
+                            ..context_trigger(&context, &mut globals.uid_counter, &info)
                         }
                         .context_parameters(&context),
                         globals.trigger_order,
@@ -767,8 +836,8 @@ pub fn compile_scope(
 
                                 for c in &mut new_contexts {
                                     // reset all variables per context
-                                    (*c).variables = context.variables.clone();
-                                    (*c).variables.insert(f.symbol.clone(), element);
+                                    c.variables = context.variables.clone();
+                                    c.variables.insert(f.symbol.clone(), element);
                                 }
 
                                 let new_info = info.clone(); // file position info
@@ -824,7 +893,7 @@ pub fn compile_scope(
 
                                 for c in &mut new_contexts {
                                     // reset all variables per context
-                                    (*c).variables = context.variables.clone();
+                                    c.variables = context.variables.clone();
                                     let key_stored = store_const_value(
                                         // store the dict key
                                         Value::Str(k.clone()),
@@ -839,7 +908,7 @@ pub fn compile_scope(
                                         globals,
                                         c,
                                     );
-                                    (*c).variables.insert(f.symbol.clone(), stored);
+                                    c.variables.insert(f.symbol.clone(), stored);
                                 }
 
                                 let new_info = info.clone(); // file position info
@@ -889,14 +958,14 @@ pub fn compile_scope(
                             for ch in s.chars() {
                                 //println!("{}", new_contexts.len());
                                 for c in &mut new_contexts {
-                                    (*c).variables = context.variables.clone();
+                                    c.variables = context.variables.clone();
                                     let stored = store_const_value(
                                         Value::Str(ch.to_string()),
                                         1,
                                         globals,
                                         c,
                                     );
-                                    (*c).variables.insert(f.symbol.clone(), stored);
+                                    c.variables.insert(f.symbol.clone(), stored);
                                 }
 
                                 let new_info = info.clone();
@@ -950,8 +1019,8 @@ pub fn compile_scope(
                                 let element =
                                     store_value(Value::Number(num as f64), 0, globals, &context);
                                 for c in &mut new_contexts {
-                                    (*c).variables = context.variables.clone();
-                                    (*c).variables.insert(f.symbol.clone(), element);
+                                    c.variables = context.variables.clone();
+                                    c.variables.insert(f.symbol.clone(), element);
                                 }
 
                                 let new_info = info.clone();
@@ -1003,7 +1072,7 @@ pub fn compile_scope(
             Break => {
                 //set all contexts to broken
                 for c in &mut contexts {
-                    (*c).broken = Some((info.clone(), BreakType::Loop));
+                    c.broken = Some((info.clone(), BreakType::Loop));
                 }
                 break;
             }
@@ -1011,7 +1080,7 @@ pub fn compile_scope(
             Continue => {
                 //set all contexts to broken
                 for c in &mut contexts {
-                    (*c).broken = Some((info.clone(), BreakType::ContinueLoop));
+                    c.broken = Some((info.clone(), BreakType::ContinueLoop));
                 }
                 break;
             }
@@ -1044,7 +1113,7 @@ pub fn compile_scope(
                 if !statement.arrow {
                     //set all contexts to broken
                     for c in &mut contexts {
-                        (*c).broken = Some((info.clone(), BreakType::Macro));
+                        c.broken = Some((info.clone(), BreakType::Macro));
                     }
                     break;
                 }
@@ -1073,7 +1142,7 @@ pub fn compile_scope(
         let mut to_be_removed = Vec::new();
 
         for (i, c) in contexts.iter().enumerate() {
-            if c.broken != None {
+            if c.broken.is_some() {
                 broken_contexts.push(c.clone());
                 to_be_removed.push(i)
             }
@@ -1106,7 +1175,7 @@ pub fn compile_scope(
         //try to merge contexts
         //if statement_index < statements.len() - 1 {
         loop {
-            if !merge_contexts(&mut contexts, globals) {
+            if !merge_contexts(&mut contexts, globals, &info) {
                 break;
             }
         }
@@ -1172,7 +1241,7 @@ pub fn import_module(
             .clone()
             .parent()
             .expect("Your file must be in a folder to import modules!")
-            .join(&p),
+            .join(p),
 
         ImportType::Lib(name) => {
             let mut outpath = info.includes[0].clone();
@@ -1268,7 +1337,7 @@ pub fn import_module(
     }
 
     let stored_path = globals.path.clone();
-    (*globals).path = module_path.clone();
+    globals.path = module_path.clone();
 
     let mut new_info = info;
 
@@ -1290,7 +1359,7 @@ pub fn import_module(
             });
         }
     }
-    (*globals).path = stored_path;
+    globals.path = stored_path;
 
     if let Some(stored_impl) = stored_impl {
         //change and delete from impls
@@ -1305,7 +1374,7 @@ pub fn import_module(
             }
         }
         for (k1, k2) in to_be_deleted {
-            (*globals).implementations.get_mut(&k1).unwrap().remove(&k2);
+            globals.implementations.get_mut(&k1).unwrap().remove(&k2);
         }
 
         //merge impls
@@ -1323,7 +1392,7 @@ pub fn import_module(
             .collect()
     } else {
         for (_, c) in &mut returns {
-            (*c).variables = context.variables.clone();
+            c.variables = context.variables.clone();
         }
 
         returns
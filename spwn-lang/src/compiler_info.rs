@@ -9,6 +9,14 @@ pub struct CompilerInfo {
     pub current_module: String, // empty string means script
     pub pos: FileRange,
     pub includes: Vec<PathBuf>,
+    // the first macro call on the way here, for source maps
+    pub caller: Option<(PathBuf, FileRange)>,
+}
+
+impl Default for CompilerInfo {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CompilerInfo {
@@ -20,6 +28,7 @@ impl CompilerInfo {
             current_module: String::new(),
             pos: ((0, 0), (0, 0)),
             includes: vec![],
+            caller: None,
         }
     }
 }
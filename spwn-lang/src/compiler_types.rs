@@ -9,7 +9,7 @@ use crate::value::*;
 //use std::boxed::Box;
 use crate::compiler_info::CompilerInfo;
 use crate::value_storage::*;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 
 use smallvec::{smallvec, SmallVec};
@@ -42,15 +42,13 @@ pub enum BreakType {
 pub struct FunctionId {
     pub parent: Option<usize>, //index of parent id, if none it is a top-level id
     pub width: Option<u32>,    //width of this id, is none when its not calculated yet
-    //pub name: String,          //name of this id, used for the label
+    pub name: Option<String>, //name of this id (the variable or macro it's from), used for the label
+    pub site: Option<String>, //where in the script this id was made, used for the label
     pub obj_list: Vec<(GdObj, usize)>, //list of objects in this function id, + their order id
+    pub file: Option<PathBuf>, //file this id was made in
 }
 
 pub type SyncPartId = usize;
-pub struct SyncGroup {
-    pub parts: Vec<SyncPartId>,
-    pub groups_used: Vec<ArbitraryId>, // groups that are already used by this sync group, and can be reused in later parts
-}
 
 pub fn handle_operator(
     value1: StoredValue,
@@ -64,7 +62,7 @@ pub fn handle_operator(
         if let Some(val) =
             globals.stored_values[value1]
                 .clone()
-                .member(macro_name.to_string(), &context, globals)
+                .member(macro_name.to_string(), context, globals)
         {
             if let Value::Macro(m) = globals.stored_values[val].clone() {
                 if m.args.is_empty() {
@@ -78,7 +76,7 @@ pub fn handle_operator(
                 if let Some(target_typ) = m.args[0].3 {
                     let pat = &globals.stored_values[target_typ].clone();
 
-                    if !val2.matches_pat(pat, &info, globals, context)? {
+                    if !val2.matches_pat(pat, info, globals, context)? {
                         //if types dont match, act as if there is no macro at all
                         return Ok(smallvec![(
                             store_value(
@@ -87,17 +85,23 @@ pub fn handle_operator(
                                     vec![value1, value2],
                                     info.clone(),
                                     globals,
-                                    &context,
+                                    context,
                                 )?,
                                 1,
                                 globals,
-                                &context,
+                                context,
                             ),
                             context.clone(),
                         )]);
                     }
                 }
 
+                let mut call_info = info.clone();
+                if call_info.caller.is_none() {
+                    call_info.caller = Some((info.current_file.clone(), info.pos));
+                }
+                call_info.path.push(macro_name.to_string());
+
                 let (values, _) = execute_macro(
                     (
                         *m,
@@ -114,7 +118,7 @@ pub fn handle_operator(
                     context,
                     globals,
                     value1,
-                    info.clone(),
+                    call_info,
                 )?;
                 values
             } else {
@@ -125,11 +129,11 @@ pub fn handle_operator(
                             vec![value1, value2],
                             info.clone(),
                             globals,
-                            &context,
+                            context,
                         )?,
                         1,
                         globals,
-                        &context,
+                        context,
                     ),
                     context.clone(),
                 )]
@@ -142,11 +146,11 @@ pub fn handle_operator(
                         vec![value1, value2],
                         info.clone(),
                         globals,
-                        &context
+                        context
                     )?,
                     1,
                     globals,
-                    &context,
+                    context,
                 ),
                 context.clone(),
             )]
@@ -198,7 +202,7 @@ impl ast::Expression {
                 //only eval the first one on Or and And
                 let (or_overwritten, and_overwritten) =
                     if let Some(imp) = globals.implementations.get(&5) {
-                        (imp.get("_or_") != None, imp.get("_and_") != None)
+                        (imp.get("_or_").is_some(), imp.get("_and_").is_some())
                     } else {
                         (false, false)
                     };
@@ -453,13 +457,13 @@ Should be used like this: value.macro(arguments)".to_string(), info
 
     // stop break chain
     for c in &mut compiled.0 {
-        if let Some((i, BreakType::Loop)) = &(*c).broken {
+        if let Some((i, BreakType::Loop)) = &c.broken {
             return Err(RuntimeError::RuntimeError {
                 message: "break statement is never used".to_string(),
                 info: i.clone(),
             });
         }
-        (*c).broken = None;
+        c.broken = None;
     }
 
     let returns = if compiled.1.is_empty() {
@@ -473,7 +477,7 @@ Should be used like this: value.macro(arguments)".to_string(), info
             .iter()
             .map(|x| {
                 //set mutable to false
-                (*globals.stored_values.map.get_mut(&x.0).unwrap()).mutable = false;
+                globals.stored_values.map.get_mut(&x.0).unwrap().mutable = false;
                 (
                     x.0,
                     Context {
@@ -559,7 +563,7 @@ pub fn eval_dict(
     inner_returns.extend(returns);
     let mut out = Returns::new();
     for expressions in evaled {
-        let mut dict_out: HashMap<String, StoredValue> = HashMap::new();
+        let mut dict_out: BTreeMap<String, StoredValue> = BTreeMap::new();
         for (expr_index, def) in dict.iter().enumerate() {
             match def {
                 ast::DictDef::Def(d) => {
@@ -584,7 +588,7 @@ pub fn eval_dict(
             };
         }
         out.push((
-            store_value(Value::Dict(dict_out), 1, globals, &context),
+            store_value(Value::Dict(dict_out), 1, globals, context),
             expressions.1,
         ));
     }
@@ -601,6 +605,7 @@ impl ast::CompoundStatement {
     ) -> Result<(TriggerFunction, Returns), RuntimeError> {
         //create the function context
         let mut new_context = context.next_fn_id(globals);
+        globals.mark_fn_id(new_context.func_id, None, &info);
 
         //pick a start group
         let start_group = if let Some(g) = start_group {
@@ -29,6 +29,12 @@ pub struct Context {
     pub sync_part: SyncPartId,
 }
 
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Context {
     pub fn new() -> Context {
         Context {
@@ -47,10 +53,14 @@ impl Context {
     }
 
     pub fn next_fn_id(&self, globals: &mut Globals) -> Context {
-        (*globals).func_ids.push(FunctionId {
+        let file = globals.func_ids[self.func_id].file.clone();
+        globals.func_ids.push(FunctionId {
             parent: Some(self.func_id),
             obj_list: Vec::new(),
             width: None,
+            name: None,
+            site: None,
+            file,
         });
 
         let mut out = self.clone();
@@ -63,6 +73,7 @@ impl Context {
 pub fn merge_contexts(
     contexts: &mut SmallVec<[Context; CONTEXT_MAX]>,
     globals: &mut Globals,
+    info: &CompilerInfo,
 ) -> bool {
     let mut mergable_ind = Vec::<usize>::new();
     let mut ref_c = 0;
@@ -76,7 +87,7 @@ pub fn merge_contexts(
             }
             let ref_c = &contexts[ref_c];
 
-            if (ref_c.broken == None) != (c.broken == None) {
+            if ref_c.broken.is_none() != c.broken.is_none() {
                 continue;
             }
             let mut not_eq = false;
@@ -120,15 +131,15 @@ pub fn merge_contexts(
         let mut params = HashMap::new();
         params.insert(51, ObjParam::Group(new_group));
         params.insert(1, ObjParam::Number(1268.0));
-        (*globals).trigger_order += 1;
+        globals.trigger_order += 1;
 
-        (*globals).func_ids[context.func_id].obj_list.push((
+        globals.func_ids[context.func_id].obj_list.push((
             GdObj {
                 params,
 
-                ..context_trigger(&context, &mut globals.uid_counter)
+                ..context_trigger(context, &mut globals.uid_counter, info)
             }
-            .context_parameters(&context),
+            .context_parameters(context),
             globals.trigger_order,
         ))
     };
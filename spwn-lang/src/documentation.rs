@@ -10,7 +10,7 @@ use crate::value::*;
 use std::fs::File;
 
 use std::path::PathBuf;
-use std::{collections::HashMap, env::current_dir};
+use std::{collections::BTreeMap, env::current_dir};
 fn create_doc_file(mut dir: PathBuf, name: String, content: &str) {
     use std::io::Write;
     dir.push(format!("{}.md", name));
@@ -93,7 +93,7 @@ pub fn document_lib(path: &str) -> Result<(), RuntimeError> {
     if !implementations.is_empty() && implementations.iter().any(|(_, a)| !a.is_empty()) {
         doc += "# Type Implementations:\n";
 
-        let mut list: Vec<(&u16, HashMap<String, usize>)> = implementations
+        let mut list: Vec<(&u16, BTreeMap<String, usize>)> = implementations
             .iter()
             .filter(|(_, a)| !a.is_empty())
             .map(|(key, val)| {
@@ -101,7 +101,7 @@ pub fn document_lib(path: &str) -> Result<(), RuntimeError> {
                     key,
                     val.iter()
                         .map(|(key, val)| (key.clone(), val.0))
-                        .collect::<HashMap<String, usize>>(),
+                        .collect::<BTreeMap<String, usize>>(),
                 )
             })
             .collect();
@@ -129,20 +129,20 @@ pub fn document_lib(path: &str) -> Result<(), RuntimeError> {
     Ok(())
 }
 
-fn document_dict(dict: &HashMap<String, usize>, globals: &mut Globals) -> String {
+fn document_dict(dict: &BTreeMap<String, usize>, globals: &mut Globals) -> String {
     let mut doc = String::new(); //String::from("<details>\n<summary> View members </summary>\n");
 
     let mut macro_list: Vec<(&String, &usize)> = dict
         .iter()
         .filter(|x| matches!(globals.stored_values[*x.1], Value::Macro(_)))
         .collect();
-    macro_list.sort_by(|a, b| a.0.cmp(&b.0));
+    macro_list.sort_by(|a, b| a.0.cmp(b.0));
 
     let mut val_list: Vec<(&String, &usize)> = dict
         .iter()
         .filter(|x| !matches!(globals.stored_values[*x.1], Value::Macro(_)))
         .collect();
-    val_list.sort_by(|a, b| a.0.cmp(&b.0));
+    val_list.sort_by(|a, b| a.0.cmp(b.0));
 
     let mut document_member = |key: &String, val: &usize| -> String {
         let mut member_doc = String::new();
@@ -173,7 +173,7 @@ fn document_dict(dict: &HashMap<String, usize>, globals: &mut Globals) -> String
             doc += "\n## Macros:\n";
         }
         for (key, val) in macro_list.iter() {
-            doc += &document_member(*key, *val)
+            doc += &document_member(key, val)
         }
     }
     if !val_list.is_empty() {
@@ -181,7 +181,7 @@ fn document_dict(dict: &HashMap<String, usize>, globals: &mut Globals) -> String
             doc += "## Other values:\n";
         }
         for (key, val) in val_list.iter() {
-            doc += &document_member(*key, *val)
+            doc += &document_member(key, val)
         }
     }
     doc
@@ -213,7 +213,7 @@ fn document_macro(mac: &Macro, globals: &mut Globals) -> String {
             }
             i += 1;
 
-            if arg.1 != None {
+            if arg.1.is_some() {
                 arg_string += &format!("| {} | `{}` |", i, arg.0);
             } else {
                 arg_string += &format!("| {} | **`{}`** |", i, arg.0);
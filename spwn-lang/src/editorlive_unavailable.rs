@@ -1,3 +1,5 @@
-pub fn editor_paste(message: &str) -> Result<bool, String> {
-    Err(String::from("Your device does not currently support live editing"))
+pub fn editor_paste(_message: &str) -> Result<bool, String> {
+    Err(String::from(
+        "Your device does not currently support live editing",
+    ))
 }
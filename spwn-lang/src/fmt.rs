@@ -40,7 +40,7 @@ fn element_list(elements: &[impl SpwnFmt], open: char, closing: char, ind: Inden
 
     let last = elements.len() - 1;
 
-    for (_i, el) in elements.iter().enumerate() {
+    for el in elements.iter() {
         let text = el.fmt(0);
 
         sum += text.lines().next().unwrap().len();
@@ -87,7 +87,7 @@ fn element_list(elements: &[impl SpwnFmt], open: char, closing: char, ind: Inden
         }
 
         let mut last_elem_lines = last_elem.lines();
-        out += &last_elem_lines.next().unwrap();
+        out += last_elem_lines.next().unwrap();
 
         for line in last_elem_lines {
             out += &format!("\n{}{}", tabs(ind), line);
@@ -172,8 +172,8 @@ mod tests {
                 */
 
 //bruh
-    //bruh
-        //bruh
+//bruh
+//bruh
         "
                 ),
                 0
@@ -455,12 +455,6 @@ impl SpwnFmt for UnaryOperator {
     }
 }
 
-impl SpwnFmt for Definition {
-    fn fmt(&self, ind: Indent) -> String {
-        format!("let {} = {}", self.symbol, self.value.fmt(ind))
-    }
-}
-
 impl SpwnFmt for Error {
     fn fmt(&self, ind: Indent) -> String {
         format!("error {}", self.message.fmt(ind))
@@ -1,6 +1,8 @@
 ///types and functions used by the compiler
 use crate::builtin::*;
-use crate::levelstring::GdObj;
+use crate::levelstring::{GdObj, LevelSettings};
+use crate::lockfile::{IdOrigin, IdOrigins};
+use crate::target::TriggerTable;
 
 use crate::compiler_types::*;
 use crate::value::*;
@@ -22,7 +24,6 @@ pub struct Globals {
 
     pub path: PathBuf,
 
-    pub lowest_y: HashMap<u32, u16>,
     pub stored_values: ValStorage,
     pub val_id: usize,
 
@@ -31,6 +32,13 @@ pub struct Globals {
 
     pub func_ids: Vec<FunctionId>,
     pub objects: Vec<GdObj>,
+    // objects that were already in the level before building
+    pub level_objects: Vec<GdObj>,
+    pub level_settings: LevelSettings,
+    // where the arbitrary IDs were made, for the lockfile
+    pub id_origins: IdOrigins,
+    // triggers added with $.register_trigger, for the optimizer
+    pub trigger_table: TriggerTable,
 
     pub prev_imports: HashMap<ImportType, (Value, Implementations)>,
 
@@ -38,8 +46,6 @@ pub struct Globals {
 
     pub uid_counter: usize,
     pub implementations: Implementations,
-
-    pub sync_groups: Vec<SyncGroup>,
 }
 
 impl Globals {
@@ -99,6 +105,78 @@ impl Globals {
         find_key_for_value(&self.type_ids, typ).unwrap().clone()
     }
 
+    // file:line:column of the code being compiled
+    pub fn site(&self, info: &CompilerInfo) -> String {
+        // paths are relative to the main script, so the lockfile works in other folders
+        let file = match self.path.parent() {
+            Some(dir) => info
+                .current_file
+                .strip_prefix(dir)
+                .unwrap_or(&info.current_file),
+            None => &info.current_file,
+        };
+        format!(
+            "{}:{}:{}",
+            file.to_string_lossy(),
+            info.pos.0 .0,
+            info.pos.0 .1 + 1
+        )
+    }
+
+    // remembers where a function id was made, for laying out and labelling its triggers
+    pub fn mark_fn_id(&mut self, func_id: usize, name: Option<String>, info: &CompilerInfo) {
+        let site = self.site(info);
+        let func_id = &mut self.func_ids[func_id];
+        func_id.file = Some(info.current_file.clone());
+        func_id.site = Some(site);
+        // if it isn't given a name, it's named after the macro it's made in
+        func_id.name = match name {
+            Some(n) => Some(n),
+            None if info.path.len() > 1 => info.path.last().cloned(),
+            None => None,
+        };
+    }
+
+    pub fn add_id_origin(&mut self, class_index: usize, id: Id, info: &CompilerInfo) {
+        if let Id::Arbitrary(id) = id {
+            let site = self.site(info);
+            self.id_origins[class_index].insert(id, IdOrigin { site, name: None });
+        }
+    }
+
+    // names an arbitrary ID after the variable it's first stored in
+    pub fn name_id_origin(&mut self, p: StoredValue, name: &str) {
+        // trigger functions name the function id they start, for --debug-labels
+        if let Value::TriggerFunc(f) = &self.stored_values[p] {
+            let group = f.start_group;
+            if let Some(func_id) = self
+                .func_ids
+                .iter_mut()
+                .rev()
+                .find(|f| matches!(f.obj_list.first(), Some((obj, _)) if obj.has_group(group)))
+            {
+                if func_id.name.is_none() {
+                    func_id.name = Some(name.to_string());
+                }
+            }
+        }
+        let (class_index, id) = match &self.stored_values[p] {
+            Value::Group(g) => (0, g.id),
+            Value::Color(c) => (1, c.id),
+            Value::Block(b) => (2, b.id),
+            Value::Item(i) => (3, i.id),
+            Value::TriggerFunc(f) => (0, f.start_group.id),
+            _ => return,
+        };
+        if let Id::Arbitrary(id) = id {
+            if let Some(origin) = self.id_origins[class_index].get_mut(&id) {
+                if origin.name.is_none() {
+                    origin.name = Some(name.to_string());
+                }
+            }
+        }
+    }
+
     pub fn new(path: PathBuf) -> Self {
         let storage = ValStorage::new();
         let mut globals = Globals {
@@ -108,8 +186,6 @@ impl Globals {
             closed_items: 0,
             path,
 
-            lowest_y: HashMap::new(),
-
             type_ids: HashMap::new(),
 
             prev_imports: HashMap::new(),
@@ -122,14 +198,17 @@ impl Globals {
             func_ids: vec![FunctionId {
                 parent: None,
                 width: None,
+                name: None,
+                site: None,
                 obj_list: Vec::new(),
+                file: None,
             }],
             objects: Vec::new(),
+            level_objects: Vec::new(),
+            level_settings: LevelSettings::default(),
+            id_origins: Default::default(),
+            trigger_table: TriggerTable::new(),
             implementations: HashMap::new(),
-            sync_groups: vec![SyncGroup {
-                parts: vec![0],
-                groups_used: Vec::new(),
-            }],
         };
 
         let mut add_type = |name: &str, id: u16| {
@@ -1,4 +1,17 @@
 // instant count algebra :pog:
+// finds chains and trees of instant count triggers and rebuilds them
+// from the smallest formula that activates the same groups
+use crate::ast::ObjectMode;
+use crate::builtin::{Group, Id, Item};
+use crate::levelstring::{GdObj, ObjParam};
+use crate::optimize::{
+    create_spawn_trigger, reads_writes, IdData, ObjPtr, Trigger, TriggerGang, TriggerNetwork,
+    Triggerlist, NO_GROUP,
+};
+use crate::target::{Target, TriggerRole};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum IcExpr {
     Or(Box<IcExpr>, Box<IcExpr>),
@@ -10,6 +23,11 @@ enum IcExpr {
     LessThan(Item, i32),
 }
 
+// the searches are exponential, so big formulas are left as they are
+const MAX_INPUTS: usize = 512;
+const MAX_FORMULAS: usize = 400;
+const MAX_STEPS: usize = 20000;
+
 #[derive(Debug, Clone, Eq)]
 struct HeapItem {
     complexity: (u16, u16),
@@ -23,14 +41,14 @@ impl PartialEq for HeapItem {
 }
 impl PartialOrd for HeapItem {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.complexity.cmp(&other.complexity))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for HeapItem {
     fn cmp(&self, other: &Self) -> Ordering {
-        //not the same as the original implementation, might have to change
-        (self.complexity.0 + self.complexity.1).cmp(&(other.complexity.0 + other.complexity.1))
+        // fewest operations first, then fewest ands (every and needs an extra group)
+        self.complexity.cmp(&other.complexity)
     }
 }
 
@@ -43,35 +61,28 @@ impl HeapItem {
     }
 }
 
-use std::cmp::Reverse;
-use std::collections::BinaryHeap;
-type CriticalValueSets = HashMap<Item, HashSet<i32>>;
+// ordered, so the same formula always gives the same answer
+type CriticalValueSets = BTreeMap<Item, BTreeSet<i32>>;
 
 // Returns a map from each variable to the set of values such that the formula
 // might evaluate differently for variable = value-1 versus variable = value.
 fn get_critical_value_sets(formula: &IcExpr, result: &mut CriticalValueSets) {
-    let mut insert_to_result = |item, num: &i32| {
-        if let Some(set) = result.get_mut(item) {
-            set.insert(*num);
-        } else {
-            let mut new_set = HashSet::new();
-            new_set.insert(*num);
-            result.insert(*item, new_set);
-        }
+    let mut insert_to_result = |item: &Item, num: i32| {
+        result.entry(*item).or_default().insert(num);
     };
     match formula {
         IcExpr::True | IcExpr::False => (),
-        IcExpr::LessThan(item, num) => insert_to_result(item, num),
+        IcExpr::LessThan(item, num) => insert_to_result(item, *num),
         IcExpr::Equals(item, num) => {
-            insert_to_result(item, num);
-            insert_to_result(item, &(*num + 1));
+            insert_to_result(item, *num);
+            insert_to_result(item, *num + 1);
         }
         IcExpr::MoreThan(item, num) => {
-            insert_to_result(item, &(*num + 1));
+            insert_to_result(item, *num + 1);
         }
         IcExpr::And(lhs, rhs) | IcExpr::Or(lhs, rhs) => {
-            get_critical_value_sets(&**lhs, result); //ladies and gentlemen, the penis operator
-            get_critical_value_sets(&**rhs, result);
+            get_critical_value_sets(lhs, result);
+            get_critical_value_sets(rhs, result);
         }
     };
 }
@@ -102,25 +113,16 @@ fn enumerate_truth_table_inputs(
             dict
         })
         .collect()
-
-    // def enumerate_truth_table_inputs(critical_value_sets):
-    //     variables, value_sets = zip(*critical_value_sets.items())
-    //     return [
-    //         dict(zip(variables, values))
-    //         for values in product(*({-inf} | value_set for value_set in value_sets))
-    //     ]
 }
 
 // Returns both constants and all single comparisons whose critical value set is
 // a subset of the given ones.
 fn enumerate_useful_primitives(critical_value_sets: &CriticalValueSets) -> Vec<IcExpr> {
-    let mut out = Vec::new();
-    out.push(IcExpr::True);
-    out.push(IcExpr::False);
+    let mut out = vec![IcExpr::True, IcExpr::False];
     for (variable, value_set) in critical_value_sets.iter() {
         for value in value_set {
             out.push(IcExpr::LessThan(*variable, *value));
-            if let Some(_) = value_set.get(&(value + 1)) {
+            if value_set.contains(&(value + 1)) {
                 out.push(IcExpr::Equals(*variable, *value));
             }
             out.push(IcExpr::MoreThan(*variable, *value - 1));
@@ -129,25 +131,28 @@ fn enumerate_useful_primitives(critical_value_sets: &CriticalValueSets) -> Vec<I
     out
 }
 
-// Evaluates the formula recursively on the given input.
-fn evaluate(formula: &IcExpr, input: &HashMap<Item, i32>) -> bool {
+// How many times the network built from the formula activates its target on the given input.
+// Every side of an or is its own trigger, so both sides can activate the target.
+// Counts are capped, since anything above the cap can never be part of the answer.
+fn evaluate(formula: &IcExpr, input: &HashMap<Item, i32>, cap: u8) -> u8 {
     match formula {
-        IcExpr::True => true,
-        IcExpr::False => false,
-        IcExpr::LessThan(item, num) => input[item] < *num,
-        IcExpr::Equals(item, num) => input[item] == *num,
-        IcExpr::MoreThan(item, num) => input[item] > *num,
-        IcExpr::And(e1, e2) => evaluate(&**e1, input) && evaluate(&**e2, input),
-        IcExpr::Or(e1, e2) => evaluate(&**e1, input) || evaluate(&**e2, input),
+        IcExpr::True => 1,
+        IcExpr::False => 0,
+        IcExpr::LessThan(item, num) => (input[item] < *num) as u8,
+        IcExpr::Equals(item, num) => (input[item] == *num) as u8,
+        IcExpr::MoreThan(item, num) => (input[item] > *num) as u8,
+        IcExpr::And(e1, e2) => (evaluate(e1, input, cap) as u16 * evaluate(e2, input, cap) as u16)
+            .min(cap as u16) as u8,
+        IcExpr::Or(e1, e2) => (evaluate(e1, input, cap) as u16 + evaluate(e2, input, cap) as u16)
+            .min(cap as u16) as u8,
     }
 }
-//Evaluates the formula on the many inputs, packing the values into an integer.
-fn get_truth_table(formula: &IcExpr, inputs: &Vec<HashMap<Item, i32>>) -> u64 {
-    let mut truth_table = 0;
-    for input in inputs {
-        truth_table = (truth_table << 1) + evaluate(formula, input) as u64;
-    }
-    truth_table
+//Evaluates the formula on the many inputs
+fn get_truth_table(formula: &IcExpr, inputs: &[HashMap<Item, i32>], cap: u8) -> Vec<u8> {
+    inputs
+        .iter()
+        .map(|input| evaluate(formula, input, cap))
+        .collect()
 }
 
 // Returns (the number of operations in the formula, the number of Ands).
@@ -156,36 +161,74 @@ fn get_complexity(formula: &IcExpr) -> (u16, u16) {
         IcExpr::True | IcExpr::False => (0, 0),
         IcExpr::LessThan(_, _) | IcExpr::MoreThan(_, _) | IcExpr::Equals(_, _) => (1, 0),
         IcExpr::And(lhs, rhs) => {
-            let (ops_lhs, ands_lhs) = get_complexity(&**lhs);
-            let (ops_rhs, ands_rhs) = get_complexity(&**rhs);
+            let (ops_lhs, ands_lhs) = get_complexity(lhs);
+            let (ops_rhs, ands_rhs) = get_complexity(rhs);
             (ops_lhs + 1 + ops_rhs, ands_lhs + 1 + ands_rhs)
         }
         IcExpr::Or(lhs, rhs) => {
-            let (ops_lhs, ands_lhs) = get_complexity(&**lhs);
-            let (ops_rhs, ands_rhs) = get_complexity(&**rhs);
+            let (ops_lhs, ands_lhs) = get_complexity(lhs);
+            let (ops_rhs, ands_rhs) = get_complexity(rhs);
             (ops_lhs + 1 + ops_rhs, ands_lhs + ands_rhs)
         }
     }
 }
-fn simplify_ic_expr_full(target_formula: IcExpr) -> IcExpr {
-    println!("\nstart: {:?}\n", target_formula);
-    let mut critical_value_sets = HashMap::new();
+
+// the number of triggers build_instant_count_network makes for the formula
+fn trigger_count(formula: &IcExpr) -> usize {
+    match formula {
+        IcExpr::False => 0,
+        IcExpr::True | IcExpr::LessThan(_, _) | IcExpr::MoreThan(_, _) | IcExpr::Equals(_, _) => 1,
+        IcExpr::And(lhs, rhs) | IcExpr::Or(lhs, rhs) => trigger_count(lhs) + trigger_count(rhs),
+    }
+}
+
+// Finds the simplest formula that activates its target the same number of times
+// as the given one, for every value of the items.
+// Returns None if the formula is too big to search.
+fn simplify_ic_expr_full(target_formula: IcExpr) -> Option<IcExpr> {
+    let mut critical_value_sets = BTreeMap::new();
     get_critical_value_sets(&target_formula, &mut critical_value_sets);
+
+    let mut input_count = 1usize;
+    for set in critical_value_sets.values() {
+        input_count = input_count.saturating_mul(set.len() + 1);
+    }
+    if input_count > MAX_INPUTS {
+        return None;
+    }
     let inputs = enumerate_truth_table_inputs(&critical_value_sets);
-    let target_truth_table = get_truth_table(&target_formula, &inputs);
-    let mut best = HashMap::<u64, IcExpr>::new();
+
+    let max = get_truth_table(&target_formula, &inputs, u8::MAX)
+        .into_iter()
+        .max()
+        .unwrap_or(0);
+    if max == u8::MAX {
+        return None;
+    }
+    let cap = max + 1;
+    let target_truth_table = get_truth_table(&target_formula, &inputs, cap);
+
+    let mut best = HashMap::<Vec<u8>, IcExpr>::new();
+    // in the order they were found
+    let mut found = Vec::<IcExpr>::new();
     let mut heap: BinaryHeap<Reverse<HeapItem>> = enumerate_useful_primitives(&critical_value_sets)
-        .iter()
-        .map(|a| Reverse(HeapItem::new(a.clone())))
+        .into_iter()
+        .map(|a| Reverse(HeapItem::new(a)))
         .collect();
-    while let None = best.get(&target_truth_table) {
-        let formula = heap.pop().unwrap().0.formula;
-        let truth_table = get_truth_table(&formula, &inputs);
-        if let Some(_) = best.get(&truth_table) {
+
+    let mut steps = 0;
+    while !best.contains_key(&target_truth_table) {
+        steps += 1;
+        if steps > MAX_STEPS || found.len() > MAX_FORMULAS {
+            return None;
+        }
+        let formula = heap.pop()?.0.formula;
+        let truth_table = get_truth_table(&formula, &inputs, cap);
+        if best.contains_key(&truth_table) {
             continue;
         }
 
-        for other_formula in best.values() {
+        for other_formula in &found {
             heap.push(Reverse(HeapItem::new(IcExpr::And(
                 formula.clone().into(),
                 other_formula.clone().into(),
@@ -195,11 +238,10 @@ fn simplify_ic_expr_full(target_formula: IcExpr) -> IcExpr {
                 other_formula.clone().into(),
             ))));
         }
+        found.push(formula.clone());
         best.insert(truth_table, formula);
     }
-    let out = best[&target_truth_table].clone();
-    println!("end: {:?}\n", out);
-    out
+    best.remove(&target_truth_table)
 }
 
 fn build_instant_count_network<'a>(
@@ -227,8 +269,9 @@ fn build_instant_count_network<'a>(
                 item,
                 objects,
                 network,
-                (true, false),
+                (false, true),
             );
+            add_connection(network, target);
             true
         }
 
@@ -241,16 +284,20 @@ fn build_instant_count_network<'a>(
                 0.0,
                 objects,
                 network,
-                (true, false),
+                (false, true),
             );
+            add_connection(network, target);
             true
         }
 
+        IcExpr::False => false,
+
         IcExpr::And(expr1, expr2) => {
             (*closed_group) += 1;
             let middle_group = Group {
-                id: ID::Arbitrary(*closed_group),
+                id: Id::Arbitrary(*closed_group),
             };
+            network.insert(middle_group, TriggerGang::new(Vec::new()));
             if build_instant_count_network(
                 network,
                 objects,
@@ -295,235 +342,16 @@ fn build_instant_count_network<'a>(
             );
             result1 || result2
         }
-        _ => unreachable!(),
     }
 }
 
-// fn get_instant_count_network<'a>(
-//     network: &'a mut TriggerNetwork,
-//     objects: &'a mut Triggerlist,
-//     start: (Group, usize),
-//     origin_group: Group,
-//     ignore_optimized: bool,
-//     closed_group: &mut u16,
-//     mut visited: HashSet<(Group, usize)>,
-//     backwards: bool,
-// ) -> Option<Vec<(Group, Group, IcExpr)>> {
-//     //u32: delay in millis
-//     let trigger = network.get(&start.0).unwrap().triggers[start.1];
-
-//     if visited.contains(&start) {
-//         if network[&start.0].triggers[start.1].deleted {
-//             return Some(Vec::new());
-//         } else {
-//             return None;
-//         }
-//     }
-
-//     visited.insert(start);
-//     let start_obj = &objects[trigger.obj].0.params;
-
-//     //println!("{}", network[&start.0].connections_in.len());
-//     assert_eq!(start_obj.get(&1), Some(&ObjParam::Number(1811.0)));
-//     // group in list is the end group
-//     let list: Vec<(usize, Group)>;
-//     if backwards {
-//         list = network[&start.0]
-//             .connections_in
-//             .iter()
-//             .map(|(a, b)| (*b, *a))
-//             .collect();
-//     } else if let Some(ObjParam::Group(g)) = start_obj.get(&51) {
-//         if let ID::Specific(_) = g.id {
-//             (*network.get_mut(&start.0).unwrap()).triggers[start.1].deleted = false;
-//             return None;
-//         }
-
-//         if let Some(gang) = network.get(g) {
-//             list = vec![*g; gang.triggers.len()]
-//                 .iter()
-//                 .copied()
-//                 .enumerate()
-//                 .collect();
-//         } else {
-//             //dangeling
-
-//             return Some(Vec::new());
-//         }
-//     } else {
-//         //dangling
-
-//         return Some(Vec::new());
-//     }
-
-//     if list.is_empty() {
-//         return Some(Vec::new());
-//     }
-//     let start_item = if let ObjParam::Item(i) =
-//         start_obj.get(&80).unwrap_or(&ObjParam::Item(Item {
-//             id: ID::Specific(0),
-//         })) {
-//         *i
-//     } else {
-//         Item {
-//             id: ID::Specific(0),
-//         }
-//     };
-//     let start_num =
-//         if let ObjParam::Number(a) = start_obj.get(&77).unwrap_or(&ObjParam::Number(0.0)) {
-//             *a as i32
-//         } else {
-//             0
-//         };
-//     let start_expr = match start_obj.get(&88) {
-//         Some(ObjParam::Number(1.0)) => IcExpr::MoreThan(start_item, start_num),
-//         Some(ObjParam::Number(2.0)) => IcExpr::LessThan(start_item, start_num),
-//         _ => IcExpr::Equals(start_item, start_num),
-//     };
-
-//     let mut out = HashSet::new();
-
-//     for (i, g) in list {
-//         let trigger_ptr = (g, i);
-//         let trigger = network[&trigger_ptr.0].triggers[trigger_ptr.1];
-
-//         //let full_trigger_ptr = (trigger_ptr.0, trigger_ptr.1, full_delay);
-//         let target_out = (origin_group, trigger_ptr.0, start_expr.clone());
-
-//         if trigger.optimized && !ignore_optimized {
-//             if !trigger.deleted {
-//                 out.insert(target_out);
-//             }
-//         } else if let TriggerRole::Operator = trigger.role {
-//             if backwards {
-//                 if let Some(children) = get_instant_count_network(
-//                     network,
-//                     objects,
-//                     trigger_ptr,
-//                     origin_group,
-//                     ignore_optimized,
-//                     closed_group,
-//                     visited.clone(),
-//                     true,
-//                 ) {
-//                     for el in children.iter().map(|(start_g, end_g, expr)| {
-//                         (
-//                             *start_g,
-//                             *end_g,
-//                             IcExpr::And(Box::from(start_expr.clone()), Box::from(expr.clone())),
-//                         )
-//                     }) {
-//                         out.insert(el);
-//                     }
-//                 } else {
-//                     (*network.get_mut(&trigger_ptr.0).unwrap()).triggers[trigger_ptr.1].deleted =
-//                         false;
-//                 }
-//             } else {
-//                 let forward = get_instant_count_network(
-//                     network,
-//                     objects,
-//                     trigger_ptr,
-//                     origin_group,
-//                     ignore_optimized,
-//                     closed_group,
-//                     visited.clone(),
-//                     false,
-//                 );
-//                 if network[&trigger_ptr.0].connections_in.len() > 1 {
-//                     let mut incoming_exprs = HashSet::new();
-//                     if let Some(children) = get_instant_count_network(
-//                         network,
-//                         objects,
-//                         trigger_ptr,
-//                         origin_group,
-//                         ignore_optimized,
-//                         closed_group,
-//                         visited.clone(),
-//                         true,
-//                     ) {
-//                         for el in children.iter().map(|(_, start_g, expr)| {
-//                             (
-//                                 *start_g,
-//                                 IcExpr::And(Box::from(start_expr.clone()), Box::from(expr.clone())),
-//                             )
-//                         }) {
-//                             incoming_exprs.insert(el);
-//                         }
-//                     } else {
-//                         (*network.get_mut(&trigger_ptr.0).unwrap()).triggers[trigger_ptr.1]
-//                             .deleted = false;
-//                     }
-//                 }
-//             }
-
-//             // (*network.get_mut(&trigger_ptr.0).unwrap()).triggers[trigger_ptr.1].deleted = false;
-//             // if optimize_from(network, objects, trigger_ptr, closed_group) {
-//             //     out.insert(target_out);
-//             // } else {
-//             //     (*network.get_mut(&trigger_ptr.0).unwrap()).triggers[trigger_ptr.1].deleted = true;
-//             // }
-
-//             if cross_point {
-//             } else {
-//                 out.extend(incoming_exprs)
-//             }
-//         } else if !backwards && optimize_from(network, objects, trigger_ptr, closed_group) {
-//             (*network.get_mut(&trigger_ptr.0).unwrap()).triggers[trigger_ptr.1].deleted = false;
-//             out.insert(target_out);
-//         }
-//     }
-
-//     (*network.get_mut(&start.0).unwrap()).triggers[start.1].deleted = true;
-
-//     Some(out.iter().cloned().collect())
-// }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    #[test]
-    fn it_works() {
-        let A = Item {
-            id: ID::Specific(1),
-        };
-        let B = Item {
-            id: ID::Specific(2),
-        };
-        let C = Item {
-            id: ID::Specific(3),
-        };
-        use IcExpr::*;
-
-        let expr = Or(
-            And(LessThan(A, 1).into(), Equals(A, 5).into()).into(),
-            And(MoreThan(A, 1).into(), Equals(A, 5).into()).into(),
-        );
-
-        println!("start: {:?}\n", expr);
-
-        /*
-        duplicates removed:
-        Or(And(MoreThan(B, 2), Equals(C, 2)), And(LessThan(C, 2), MoreThan(B, 2)))
-
-        ands decreased: And(MoreThan(B, 2), Or(Equals(C, 2), LessThan(C, 2)))
-
-        simplified: Some(And(MoreThan(B, 2), LessThan(C, 3)))
-
-        ((B > 2) && (C == 2)) || ((B > 2) && (C < 2))
-
-        (B > 2) && ((C == 2) || (C < 2))
-
-        (B > 2) && (C < 3)
-
-        thats pretty epic
-
-        */
-
-        println!("simplified: {:?}\n", simplify_ic_expr_full(expr));
+fn add_connection(network: &mut TriggerNetwork, group: Group) {
+    if let Some(gang) = network.get_mut(&group) {
+        gang.connections_in += 1;
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_instant_count_trigger(
     reference_trigger: Trigger,
     target_group: Group,
@@ -539,6 +367,7 @@ fn create_instant_count_trigger(
     let mut new_obj_map = HashMap::new();
     new_obj_map.insert(1, ObjParam::Number(1811.0));
     new_obj_map.insert(51, ObjParam::Group(target_group));
+    new_obj_map.insert(56, ObjParam::Bool(true));
     new_obj_map.insert(80, ObjParam::Item(item));
     new_obj_map.insert(77, ObjParam::Number(num.into()));
     new_obj_map.insert(88, ObjParam::Number(operation.into()));
@@ -547,13 +376,14 @@ fn create_instant_count_trigger(
         new_obj_map.insert(57, ObjParam::Group(g));
     }
 
-    let new_obj = GDObj {
+    let new_obj = GdObj {
         params: new_obj_map,
         func_id: reference_trigger.obj.0,
         mode: ObjectMode::Trigger,
         unique_id: objects[reference_trigger.obj].0.unique_id,
         sync_group: 0,
         sync_part: 0,
+        source: objects[reference_trigger.obj].0.source.clone(),
     };
 
     (*objects.list)[reference_trigger.obj.0]
@@ -568,23 +398,370 @@ fn create_instant_count_trigger(
         obj: obj_index,
         optimized: settings.0,
         deleted: settings.1,
-        role: TriggerRole::Operator,
+        role: TriggerRole::Func,
         ..reference_trigger
     };
 
     if let Some(ObjParam::Group(group)) = new_obj.params.get(&57) {
         match network.get_mut(group) {
-            Some(gang) => (*gang).triggers.push(new_trigger),
+            Some(gang) => gang.triggers.push(new_trigger),
             None => {
                 network.insert(*group, TriggerGang::new(vec![new_trigger]));
             }
         }
     } else {
         match network.get_mut(&NO_GROUP) {
-            Some(gang) => (*gang).triggers.push(new_trigger),
+            Some(gang) => gang.triggers.push(new_trigger),
             None => {
                 network.insert(NO_GROUP, TriggerGang::new(vec![new_trigger]));
             }
         }
     }
 }
+
+// the keys an instant count trigger can have and still be rebuilt
+// (62 and 87 are added to spawned triggers later)
+const IC_KEYS: &[u16] = &[1, 51, 56, 57, 62, 77, 80, 87, 88];
+
+// the condition of an instant count trigger that only activates its target group
+fn ic_condition(obj: &GdObj) -> Option<(IcExpr, Group)> {
+    if obj.params.get(&1) != Some(&ObjParam::Number(1811.0))
+        || obj.params.get(&56) != Some(&ObjParam::Bool(true))
+        || obj.params.keys().any(|k| !IC_KEYS.contains(k))
+    {
+        return None;
+    }
+    let target = match obj.params.get(&51) {
+        Some(ObjParam::Group(g)) => *g,
+        _ => return None,
+    };
+    let item = match obj.params.get(&80) {
+        Some(ObjParam::Item(i)) => *i,
+        _ => return None,
+    };
+    let num = match obj.params.get(&77) {
+        // far away from the edges of i32, so the critical values don't overflow
+        Some(ObjParam::Number(n)) if n.fract() == 0.0 && n.abs() < 1e9 => *n as i32,
+        None => 0,
+        _ => return None,
+    };
+    let expr = match obj.params.get(&88) {
+        Some(ObjParam::Number(n)) if *n == 0.0 => IcExpr::Equals(item, num),
+        None => IcExpr::Equals(item, num),
+        Some(ObjParam::Number(n)) if *n == 1.0 => IcExpr::MoreThan(item, num),
+        Some(ObjParam::Number(n)) if *n == 2.0 => IcExpr::LessThan(item, num),
+        _ => return None,
+    };
+    Some((expr, target))
+}
+
+// the instant count triggers in a group, and every instant count trigger they lead to
+#[derive(Default)]
+struct IcTree {
+    triggers: Vec<ObjPtr>,
+    // groups that are only activated from inside the tree
+    inner_groups: Vec<Group>,
+    // the groups the tree activates, with the condition for every way of getting there
+    leaves: Vec<(Group, Vec<IcExpr>)>,
+    reads: HashSet<Item>,
+    paths: usize,
+}
+
+// every way through the tree is its own path, so trees that branch and meet again a lot are left alone
+const MAX_PATHS: usize = 64;
+
+// returns false if the tree can't be rebuilt
+fn walk_tree(
+    network: &TriggerNetwork,
+    objects: &Triggerlist,
+    inner_groups: &HashSet<Group>,
+    triggers: &[Trigger],
+    path: Option<IcExpr>,
+    tree: &mut IcTree,
+    stack: &mut Vec<Group>,
+) -> bool {
+    for trigger in triggers {
+        let (cond, target) = ic_condition(&objects[trigger.obj].0).unwrap();
+        if let IcExpr::Equals(item, _) | IcExpr::MoreThan(item, _) | IcExpr::LessThan(item, _) =
+            &cond
+        {
+            tree.reads.insert(*item);
+        }
+        let full = match &path {
+            Some(p) => IcExpr::And(p.clone().into(), cond.into()),
+            None => cond,
+        };
+        if !tree.triggers.contains(&trigger.obj) {
+            tree.triggers.push(trigger.obj);
+        }
+
+        if inner_groups.contains(&target) {
+            if stack.contains(&target) {
+                // loop
+                return false;
+            }
+            if !tree.inner_groups.contains(&target) {
+                tree.inner_groups.push(target);
+            }
+            stack.push(target);
+            let ok = walk_tree(
+                network,
+                objects,
+                inner_groups,
+                &network[&target].triggers,
+                Some(full),
+                tree,
+                stack,
+            );
+            stack.pop();
+            if !ok {
+                return false;
+            }
+        } else {
+            tree.paths += 1;
+            if tree.paths > MAX_PATHS {
+                return false;
+            }
+            match tree.leaves.iter_mut().find(|(g, _)| *g == target) {
+                Some((_, paths)) => paths.push(full),
+                None => tree.leaves.push((target, vec![full])),
+            }
+        }
+    }
+    true
+}
+
+// collapses every chain or tree of instant count triggers that can be made with fewer triggers
+pub fn simplify_instant_count_networks(
+    network: &mut TriggerNetwork,
+    objects: &mut Triggerlist,
+    closed_group: &mut u16,
+    target: &Target,
+) {
+    // how many instant count triggers that can be rebuilt activate each group
+    let mut ic_connections = HashMap::<Group, u32>::new();
+    for gang in network.values() {
+        for trigger in &gang.triggers {
+            if let Some((_, g)) = ic_condition(&objects[trigger.obj].0) {
+                *ic_connections.entry(g).or_insert(0) += 1;
+            }
+        }
+    }
+
+    // groups with nothing but instant count triggers, that only instant count triggers activate
+    let inner_groups = network
+        .iter()
+        .filter(|(g, gang)| {
+            matches!(g.id, Id::Arbitrary(_))
+                && ic_connections.get(g) == Some(&gang.connections_in)
+                && !gang.triggers.is_empty()
+                && gang
+                    .triggers
+                    .iter()
+                    .all(|t| ic_condition(&objects[t.obj].0).is_some())
+        })
+        .map(|(g, _)| *g)
+        .collect::<HashSet<Group>>();
+
+    let mut roots = Vec::new();
+    for (group, gang) in network.iter() {
+        if inner_groups.contains(group) {
+            continue;
+        }
+        let triggers = gang
+            .triggers
+            .iter()
+            .filter(|t| ic_condition(&objects[t.obj].0).is_some())
+            .copied()
+            .collect::<Vec<Trigger>>();
+        if triggers
+            .iter()
+            .any(|t| inner_groups.contains(&ic_condition(&objects[t.obj].0).unwrap().1))
+        {
+            roots.push((*group, triggers));
+        }
+    }
+
+    for (group, root_triggers) in roots {
+        let mut tree = IcTree::default();
+        if !walk_tree(
+            network,
+            objects,
+            &inner_groups,
+            &root_triggers,
+            None,
+            &mut tree,
+            &mut Vec::new(),
+        ) {
+            continue;
+        }
+
+        // inner groups that are also activated from another tree
+        let outside = tree.inner_groups.iter().any(|g| {
+            let from_tree = tree
+                .triggers
+                .iter()
+                .filter(|t| ic_condition(&objects[**t].0).unwrap().1 == *g)
+                .count() as u32;
+            from_tree != network[g].connections_in
+        });
+        if outside {
+            continue;
+        }
+
+        // the new triggers are all in the root group, so nothing else in
+        // that group can change what they read
+        let conflict = network[&group].triggers.iter().any(|t| {
+            !tree.triggers.contains(&t.obj)
+                && reads_writes(*t, objects, target)
+                    .1
+                    .iter()
+                    .any(|w| matches!(w, IdData::Item(i) if tree.reads.contains(i)))
+        });
+        if conflict {
+            continue;
+        }
+
+        let mut simplified = Vec::new();
+        for (leaf, paths) in &tree.leaves {
+            let mut paths = paths.iter().cloned();
+            let first = paths.next().unwrap();
+            let formula = paths.fold(first, |acc, p| IcExpr::Or(acc.into(), p.into()));
+            match simplify_ic_expr_full(formula) {
+                Some(f) => simplified.push((*leaf, f)),
+                None => break,
+            }
+        }
+        if simplified.len() < tree.leaves.len()
+            || simplified
+                .iter()
+                .map(|(_, f)| trigger_count(f))
+                .sum::<usize>()
+                >= tree.triggers.len()
+        {
+            continue;
+        }
+
+        // replace the tree
+        for g in &tree.inner_groups {
+            network.remove(g);
+        }
+        network
+            .get_mut(&group)
+            .unwrap()
+            .triggers
+            .retain(|t| !tree.triggers.contains(&t.obj));
+        // a leaf can be reached through one trigger in more than one way,
+        // so count the removed triggers that activate it, not the paths
+        for (leaf, _) in &tree.leaves {
+            let removed = tree
+                .triggers
+                .iter()
+                .filter(|t| ic_condition(&objects[**t].0).unwrap().1 == *leaf)
+                .count() as u32;
+            if let Some(gang) = network.get_mut(leaf) {
+                gang.connections_in -= removed;
+            }
+        }
+        for (leaf, formula) in simplified {
+            build_instant_count_network(
+                network,
+                objects,
+                Some(group),
+                leaf,
+                formula,
+                root_triggers[0],
+                closed_group,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimize::build_network;
+    use crate::test_util::{function, group, trigger};
+
+    // an instant count trigger in `from`, activating `to` if item 1 is `num`
+    fn instant_count(from: u16, to: u16, num: f64) -> (GdObj, usize) {
+        trigger(vec![
+            (1, ObjParam::Number(1811.0)),
+            (51, group(Id::Arbitrary(to))),
+            (56, ObjParam::Bool(true)),
+            (57, group(Id::Arbitrary(from))),
+            (77, ObjParam::Number(num)),
+            (
+                80,
+                ObjParam::Item(Item {
+                    id: Id::Specific(1),
+                }),
+            ),
+        ])
+    }
+
+    #[test]
+    fn leaf_with_two_parents() {
+        let leaf = Group {
+            id: Id::Arbitrary(3),
+        };
+        let mut func_ids = vec![function(vec![
+            // two ways into group 2, which reaches the leaf (group 3)
+            // through one trigger at the end of a chain
+            instant_count(1, 2, 1.0),
+            instant_count(1, 2, 1.0),
+            instant_count(2, 6, 1.0),
+            instant_count(6, 7, 1.0),
+            instant_count(7, 3, 1.0),
+            // the leaf is also spawned from somewhere else
+            trigger(vec![
+                (1, ObjParam::Number(1268.0)),
+                (51, group(Id::Arbitrary(3))),
+                (57, group(Id::Arbitrary(4))),
+            ]),
+            // and does something
+            trigger(vec![
+                (1, ObjParam::Number(901.0)),
+                (51, group(Id::Arbitrary(5))),
+                (57, group(Id::Arbitrary(3))),
+            ]),
+        ])];
+        let target = Target::default();
+        let mut network = build_network(&func_ids, &target);
+        assert_eq!(network[&leaf].connections_in, 2);
+
+        let mut objects = Triggerlist {
+            list: &mut func_ids,
+        };
+        simplify_instant_count_networks(&mut network, &mut objects, &mut 10, &target);
+
+        // the chain is gone, and the leaf is still activated by every trigger targeting it
+        for g in [2, 6, 7].iter() {
+            assert!(!network.contains_key(&Group {
+                id: Id::Arbitrary(*g)
+            }));
+        }
+        let targeting = network
+            .values()
+            .flat_map(|gang| gang.triggers.iter())
+            .filter(|t| objects[t.obj].0.params.get(&51) == Some(&ObjParam::Group(leaf)))
+            .count();
+        assert!(targeting >= 2);
+        assert_eq!(network[&leaf].connections_in as usize, targeting);
+    }
+
+    #[test]
+    fn it_works() {
+        let a = Item {
+            id: Id::Specific(1),
+        };
+        use IcExpr::*;
+
+        let expr = Or(
+            And(LessThan(a, 1).into(), Equals(a, 5).into()).into(),
+            And(MoreThan(a, 1).into(), Equals(a, 5).into()).into(),
+        );
+
+        assert_eq!(simplify_ic_expr_full(expr), Some(Equals(a, 5)));
+    }
+}
@@ -0,0 +1,452 @@
+// tools for looking at the contents of existing levels
+use crate::builtin::*;
+use crate::compiler::{import_module, RuntimeError};
+use crate::compiler_info::CompilerInfo;
+use crate::compiler_types::ImportType;
+use crate::context::Context;
+use crate::globals::Globals;
+use crate::levelstring::*;
+use crate::print_with_color;
+use crate::value::Value;
+use crate::STD_PATH;
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use termcolor::Color;
+
+// reads the names of all object keys from the obj_props table in constants.spwn
+pub fn get_obj_prop_names(includes: Vec<PathBuf>) -> Result<HashMap<u16, String>, RuntimeError> {
+    let mut globals = Globals::new(PathBuf::new());
+    let mut info = CompilerInfo::new();
+    info.includes = includes;
+
+    let module = import_module(
+        &ImportType::Lib(format!("{}/constants.spwn", STD_PATH)),
+        &Context::new(),
+        &mut globals,
+        info.clone(),
+        false,
+    )?;
+
+    let obj_props = match &globals.stored_values[module[0].0] {
+        Value::Dict(d) => d.get("obj_props").copied(),
+        _ => None,
+    };
+    let obj_props = match obj_props.map(|p| globals.stored_values[p].clone()) {
+        Some(Value::Dict(d)) => d,
+        _ => {
+            return Err(RuntimeError::RuntimeError {
+                message: "constants.spwn has no obj_props table".to_string(),
+                info,
+            })
+        }
+    };
+
+    let mut names = HashMap::<u16, Vec<String>>::new();
+    for (name, key) in obj_props {
+        if let Value::Dict(d) = &globals.stored_values[key] {
+            if let Some(Value::Number(id)) = d.get("id").map(|id| &globals.stored_values[*id]) {
+                names.entry(*id as u16).or_default().push(name);
+            }
+        }
+    }
+
+    // some keys have more than one name (like FOLLOW, CENTER and TARGET_POS)
+    Ok(names
+        .into_iter()
+        .map(|(id, mut n)| {
+            n.sort();
+            (id, n.join("/"))
+        })
+        .collect())
+}
+
+// formats a list of ids, collapsing consecutive ids into ranges (1-4, 7, 9-10)
+pub fn format_id_list(ids: &HashSet<u16>) -> String {
+    let mut sorted = ids.iter().copied().collect::<Vec<u16>>();
+    sorted.sort_unstable();
+
+    let mut ranges = Vec::<(u16, u16)>::new();
+    for id in sorted {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == id => *end = id,
+            _ => ranges.push((id, id)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// formats the properties of an object like "TARGET: 12, SPAWN_DURATION: 0.05"
+pub fn format_obj_params(obj: &GdObj, obj_props: &HashMap<u16, String>, skip: &[u16]) -> String {
+    let mut param_list = obj.params.iter().collect::<Vec<(&u16, &ObjParam)>>();
+    param_list.sort_by(|a, b| (*a.0).cmp(b.0));
+
+    param_list
+        .iter()
+        .filter(|(key, _)| !skip.contains(key))
+        .map(|(key, val)| match obj_props.get(key) {
+            Some(name) => format!("{}: {}", name, val),
+            None => format!("{}: {}", key, val),
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn obj_id(obj: &GdObj) -> u16 {
+    match obj.params.get(&1) {
+        Some(ObjParam::Number(n)) => *n as u16,
+        _ => 0,
+    }
+}
+
+// "901 at (15, 45): TARGET: 12, ...", with the position taken out of the properties
+fn describe_obj(obj: &GdObj, obj_props: &HashMap<u16, String>) -> String {
+    let pos = |key| match obj.params.get(&key) {
+        Some(p) => p.to_string(),
+        None => "0".to_string(),
+    };
+    format!(
+        "{} at ({}, {}): {}",
+        obj_id(obj),
+        pos(2),
+        pos(3),
+        format_obj_params(obj, obj_props, &[1, 2, 3])
+    )
+}
+
+pub fn inspect_level(
+    ls: &str,
+    show_triggers: bool,
+    obj_props: &HashMap<u16, String>,
+) -> Result<(), String> {
+    let (_, objects) = parse_level_string(ls)?;
+
+    let trigger_amount = objects
+        .iter()
+        .filter(|o| o.mode == crate::ast::ObjectMode::Trigger)
+        .count();
+
+    print_with_color("Level:", Color::Magenta);
+    println!(
+        "{} objects, {} of them triggers",
+        objects.len(),
+        trigger_amount
+    );
+
+    let spwn_objects = objects
+        .iter()
+        .filter(|o| o.has_group(SPWN_SIGNATURE_GROUP))
+        .collect::<Vec<&GdObj>>();
+    if let Id::Specific(g) = SPWN_SIGNATURE_GROUP.id {
+        println!(
+            "{} objects were added by SPWN (group {})",
+            spwn_objects.len(),
+            g
+        );
+    }
+    let mut namespaces = level_namespaces(ls)?
+        .into_iter()
+        .collect::<Vec<(String, u16)>>();
+    namespaces.sort();
+    for (name, group) in namespaces {
+        let group = Group {
+            id: Id::Specific(group),
+        };
+        println!(
+            "    {} of them in the namespace \"{}\" (group {})",
+            spwn_objects.iter().filter(|o| o.has_group(group)).count(),
+            name,
+            ObjParam::Group(group)
+        );
+    }
+
+    print_with_color("\nObjects by ID:", Color::Magenta);
+    let mut counts = HashMap::<u16, usize>::new();
+    for obj in &objects {
+        *counts.entry(obj_id(obj)).or_insert(0) += 1;
+    }
+    let mut counts = counts.into_iter().collect::<Vec<(u16, usize)>>();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    for (id, count) in counts {
+        println!("{:>6}: {}", id, count);
+    }
+
+    print_with_color("\nUsed IDs:", Color::Magenta);
+    for (i, ids) in get_used_ids(ls)?.iter().enumerate() {
+        if !ids.is_empty() {
+            println!(
+                "{} {}: {}",
+                ids.len(),
+                ["groups", "colors", "block IDs", "item IDs"][i],
+                format_id_list(ids)
+            );
+        }
+    }
+
+    if !spwn_objects.is_empty() {
+        print_with_color("\nObjects added by SPWN:", Color::Magenta);
+        for obj in spwn_objects {
+            println!("{}", describe_obj(obj, obj_props));
+        }
+    }
+
+    if show_triggers {
+        print_with_color("\nTriggers:", Color::Magenta);
+        for obj in objects
+            .iter()
+            .filter(|o| o.mode == crate::ast::ObjectMode::Trigger)
+        {
+            println!("{}", describe_obj(obj, obj_props));
+        }
+    }
+
+    Ok(())
+}
+
+// key,value list of an object with the keys sorted, used to compare objects
+fn obj_signature(obj: &GdObj) -> String {
+    let mut param_list = obj.params.iter().collect::<Vec<(&u16, &ObjParam)>>();
+    param_list.sort_by(|a, b| (*a.0).cmp(b.0));
+    param_list
+        .iter()
+        .map(|(key, val)| format!("{},{}", key, val))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+// the keys that have a different value in the two objects, with both values
+fn changed_params(a: &GdObj, b: &GdObj) -> Vec<(u16, Option<String>, Option<String>)> {
+    let mut keys = a
+        .params
+        .keys()
+        .chain(b.params.keys())
+        .copied()
+        .collect::<Vec<u16>>();
+    keys.sort_unstable();
+    keys.dedup();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let val_a = a.params.get(&key).map(|v| v.to_string());
+            let val_b = b.params.get(&key).map(|v| v.to_string());
+            if val_a == val_b {
+                None
+            } else {
+                Some((key, val_a, val_b))
+            }
+        })
+        .collect()
+}
+
+// the objects that were added, removed and changed between two levels
+struct LevelDiff<'a> {
+    added: Vec<&'a GdObj>,
+    removed: Vec<&'a GdObj>,
+    changed: Vec<(&'a GdObj, &'a GdObj)>,
+    unchanged: usize,
+}
+
+fn match_objects<'a>(objects_a: &'a [GdObj], objects_b: &'a [GdObj]) -> LevelDiff<'a> {
+    // first match up all objects that are exactly the same
+    let mut unmatched_b = HashMap::<String, Vec<usize>>::new();
+    for (i, obj) in objects_b.iter().enumerate() {
+        unmatched_b.entry(obj_signature(obj)).or_default().push(i);
+    }
+    let mut removed = Vec::new();
+    let mut unchanged = 0;
+    for obj in objects_a {
+        match unmatched_b.get_mut(&obj_signature(obj)) {
+            Some(list) if !list.is_empty() => {
+                list.remove(0);
+                unchanged += 1;
+            }
+            _ => removed.push(obj),
+        }
+    }
+    let mut added = unmatched_b
+        .values()
+        .flatten()
+        .copied()
+        .collect::<Vec<usize>>();
+    added.sort_unstable();
+    let mut added = added
+        .into_iter()
+        .map(|i| &objects_b[i])
+        .collect::<Vec<&GdObj>>();
+
+    // then pair up the remaining objects with the same object ID that
+    // have the fewest different properties
+    let mut changed = Vec::new();
+    removed.retain(|obj| {
+        let best = added
+            .iter()
+            .enumerate()
+            .filter(|(_, other)| obj_id(other) == obj_id(obj))
+            .map(|(i, other)| (i, changed_params(obj, other).len()))
+            .min_by_key(|(_, len)| *len);
+        match best {
+            Some((i, len)) if len * 2 <= obj.params.len().max(added[i].params.len()) => {
+                changed.push((*obj, added.remove(i)));
+                false
+            }
+            _ => true,
+        }
+    });
+
+    LevelDiff {
+        added,
+        removed,
+        changed,
+        unchanged,
+    }
+}
+
+// compares two level strings object by object, and prints the added,
+// removed and changed objects
+pub fn diff_levels(ls_a: &str, ls_b: &str, obj_props: &HashMap<u16, String>) -> Result<(), String> {
+    let (_, objects_a) = parse_level_string(ls_a)?;
+    let (_, objects_b) = parse_level_string(ls_b)?;
+    let LevelDiff {
+        added,
+        removed,
+        changed,
+        unchanged,
+    } = match_objects(&objects_a, &objects_b);
+
+    let name = |key: u16| match obj_props.get(&key) {
+        Some(name) => name.clone(),
+        None => key.to_string(),
+    };
+
+    for obj in &removed {
+        print_with_color(&format!("- {}", describe_obj(obj, obj_props)), Color::Red);
+    }
+    for obj in &added {
+        print_with_color(&format!("+ {}", describe_obj(obj, obj_props)), Color::Green);
+    }
+    for (a, b) in &changed {
+        print_with_color(&format!("~ {}", describe_obj(a, obj_props)), Color::Yellow);
+        for (key, val_a, val_b) in changed_params(a, b) {
+            println!(
+                "    {}: {} -> {}",
+                name(key),
+                val_a.unwrap_or_else(|| "(none)".to_string()),
+                val_b.unwrap_or_else(|| "(none)".to_string())
+            );
+        }
+    }
+
+    print_with_color("\nSummary:", Color::Magenta);
+    println!(
+        "{} added, {} removed, {} changed, {} unchanged",
+        added.len(),
+        removed.len(),
+        changed.len(),
+        unchanged
+    );
+    Ok(())
+}
+
+// the cleaned level string, how many objects were removed and the ids that nothing uses anymore
+pub type CleanedLevel = (String, usize, [HashSet<u16>; 4]);
+
+// removes the SPWN objects (of one namespace) from a level string
+pub fn clean_level(ls: &str, namespace: Option<&str>) -> Result<CleanedLevel, String> {
+    let used_before = get_used_ids(ls)?;
+    let mut cleaned = ls.to_string();
+    let removed = remove_spwn_objects(&mut cleaned, namespace)?;
+    let used_after = get_used_ids(&cleaned)?;
+
+    let mut freed: [HashSet<u16>; 4] = Default::default();
+    for (i, ids) in used_before.iter().enumerate() {
+        freed[i] = ids.difference(&used_after[i]).copied().collect();
+    }
+    Ok((cleaned, removed, freed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_lists() {
+        let ids = [9, 1, 2, 3, 4, 7, 10]
+            .iter()
+            .copied()
+            .collect::<HashSet<u16>>();
+        assert_eq!(format_id_list(&ids), "1-4, 7, 9-10");
+        assert_eq!(format_id_list(&HashSet::new()), "");
+    }
+
+    #[test]
+    fn obj_params() {
+        let obj = parse_obj("1,901,2,15,3,45,51,12,10,0.5,62,1").unwrap();
+        let mut names = HashMap::new();
+        names.insert(51, "TARGET".to_string());
+        names.insert(62, "SPAWN_TRIGGERED".to_string());
+        assert_eq!(
+            format_obj_params(&obj, &names, &[1, 2, 3]),
+            "10: 0.500, TARGET: 12, SPAWN_TRIGGERED: 1"
+        );
+        assert_eq!(
+            describe_obj(&obj, &names),
+            "901 at (15, 45): 10: 0.500, TARGET: 12, SPAWN_TRIGGERED: 1"
+        );
+        // objects without a position are at the origin
+        let obj = parse_obj("1,1,57,1001").unwrap();
+        assert_eq!(describe_obj(&obj, &HashMap::new()), "1 at (0, 0): 57: 1001");
+    }
+
+    #[test]
+    fn level_diffs() {
+        let parse = |ls: &str| parse_level_string(ls).unwrap().1;
+        let a = parse("1,1,2,15,3,15;1,1,2,45,3,15;1,901,2,0,3,0,51,5,10,0.5,28,30;1,29,2,0,3,30");
+        // the second block moved, the move trigger got a new duration,
+        // and the color trigger was replaced by another object
+        let b = parse("1,1,2,15,3,15;1,1,2,75,3,15;1,901,2,0,3,0,51,5,10,1,28,30;1,8,2,0,3,30");
+        let diff = match_objects(&a, &b);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.changed.len(), 2);
+        assert_eq!(
+            changed_params(diff.changed[0].0, diff.changed[0].1),
+            vec![(2, Some("45".to_string()), Some("75".to_string()))]
+        );
+        assert_eq!(
+            changed_params(diff.changed[1].0, diff.changed[1].1),
+            vec![(10, Some("0.500".to_string()), Some("1".to_string()))]
+        );
+        assert_eq!(
+            diff.removed.iter().map(|o| obj_id(o)).collect::<Vec<u16>>(),
+            vec![29]
+        );
+        assert_eq!(
+            diff.added.iter().map(|o| obj_id(o)).collect::<Vec<u16>>(),
+            vec![8]
+        );
+    }
+
+    #[test]
+    fn cleaning_levels() {
+        let ls = "kA4,0;1,1,57,3;1,901,51,4,57,5.1001;1,1,57,6.1001,21,7;";
+        let (cleaned, removed, freed) = clean_level(ls, None).unwrap();
+        assert_eq!(cleaned, "kA4,0;1,1,57,3;");
+        assert_eq!(removed, 2);
+        assert_eq!(format_id_list(&freed[0]), "4-6, 1001");
+        assert_eq!(format_id_list(&freed[1]), "7");
+
+        let (cleaned, removed, freed) = clean_level(&cleaned, None).unwrap();
+        assert_eq!(cleaned, "kA4,0;1,1,57,3;");
+        assert_eq!(removed, 0);
+        assert!(freed.iter().all(|ids| ids.is_empty()));
+    }
+}
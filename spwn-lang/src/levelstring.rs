@@ -1,9 +1,14 @@
 // useful things for dealing with gd level data
 use crate::ast::ObjectMode;
 use crate::builtin::*;
+use crate::compiler_info::CompilerInfo;
 use crate::compiler_types::*;
 use crate::context::Context;
+use crate::lockfile::IdMaps;
+use crate::parser::FileRange;
+use crate::target::Target;
 use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum ObjParam {
@@ -68,6 +73,34 @@ pub struct GdObj {
     pub unique_id: usize,
     pub sync_group: usize,
     pub sync_part: SyncPartId,
+    pub source: Option<ObjSource>,
+}
+
+// where in the script an object was made
+#[derive(Clone, PartialEq, Debug)]
+pub struct ObjSource {
+    // the statement, or the macro call it was made in
+    pub file: PathBuf,
+    pub pos: FileRange,
+    // the name of that macro
+    pub macro_name: Option<String>,
+    // the file with the code that actually made it (like a library)
+    pub def_file: PathBuf,
+}
+
+impl ObjSource {
+    pub fn from_info(info: &CompilerInfo) -> Self {
+        let (file, pos) = match &info.caller {
+            Some((file, pos)) => (file.clone(), *pos),
+            None => (info.current_file.clone(), info.pos),
+        };
+        ObjSource {
+            file,
+            pos,
+            macro_name: info.path.get(1).cloned(),
+            def_file: info.current_file.clone(),
+        }
+    }
 }
 
 impl GdObj {
@@ -76,78 +109,183 @@ impl GdObj {
 
         (*self).clone()
     }
+
+    pub fn has_group(&self, group: Group) -> bool {
+        match self.params.get(&57) {
+            Some(ObjParam::Group(g)) => *g == group,
+            Some(ObjParam::GroupList(l)) => l.contains(&group),
+            _ => false,
+        }
+    }
+}
+
+// object ids of all triggers, used to tell triggers and normal objects apart
+// when reading objects back from a level
+pub const TRIGGER_IDS: &[u16] = &[
+    22, 23, 24, 25, 26, 27, 28, 29, 30, 55, 56, 57, 58, 59, 104, 105, 221, 717, 718, 743, 744, 899,
+    900, 901, 915, 1006, 1007, 1049, 1268, 1346, 1347, 1520, 1585, 1595, 1611, 1612, 1613, 1616,
+    1811, 1812, 1814, 1815, 1817, 1818, 1819, 1912, 1913, 1914, 1915, 1916, 1917, 1932, 1934, 1935,
+    2015, 2016, 2062, 2066, 2067, 2068,
+];
+
+// triggers that were added in 2.2
+pub const GD22_TRIGGER_IDS: &[u16] = &[
+    1934, 1935, 2015, 2016, 2062, 2066, 2067, 2068, 2899, 2900, 2901, 2903, 2904, 2905, 2907, 2909,
+    2910, 2911, 2912, 2913, 2914, 2915, 2916, 2917, 2919, 2920, 2921, 2922, 2923, 2924, 2925, 2999,
+    3006, 3007, 3008, 3009, 3010, 3011, 3012, 3013, 3014, 3015, 3016, 3017, 3018, 3019, 3020, 3021,
+    3022, 3023, 3024, 3029, 3030, 3031, 3033, 3600, 3602, 3603, 3604, 3605, 3606, 3607, 3608, 3609,
+    3612, 3613, 3614, 3615, 3617, 3618, 3619, 3620, 3640, 3641, 3642, 3660, 3661, 3662,
+];
+
+// keys that hold a bool, taken from obj_props in constants.spwn
+const BOOL_KEYS: &[u16] = &[
+    4, 5, 11, 13, 15, 16, 17, 34, 36, 41, 42, 48, 56, 58, 59, 60, 62, 64, 65, 66, 67, 70, 81, 86,
+    87, 89, 93, 94, 96, 98, 100, 102, 103, 104, 106,
+];
+
+fn parse_id(val: &str) -> Result<Id, String> {
+    match val.parse::<u16>() {
+        Ok(n) => Ok(Id::Specific(n)),
+        Err(_) => Err(format!("Invalid ID: \"{}\"", val)),
+    }
+}
+
+// decodes a single value, depending on its key and the object it belongs to
+fn parse_param(key: u16, val: &str, props: &HashMap<u16, &str>) -> Result<ObjParam, String> {
+    let obj_id = props.get(&1).copied();
+    Ok(match key {
+        57 => {
+            let mut groups = Vec::new();
+            for g in val.split('.') {
+                groups.push(Group { id: parse_id(g)? });
+            }
+            if groups.len() == 1 {
+                ObjParam::Group(groups[0])
+            } else {
+                ObjParam::GroupList(groups)
+            }
+        }
+        // pulse triggers target either a group or a color channel
+        51 => match (obj_id, props.get(&52)) {
+            (Some("1006"), Some(&"1")) => ObjParam::Group(Group { id: parse_id(val)? }),
+            (Some("1006"), _) => ObjParam::Color(Color { id: parse_id(val)? }),
+            _ => ObjParam::Group(Group { id: parse_id(val)? }),
+        },
+        71 => ObjParam::Group(Group { id: parse_id(val)? }),
+        21 | 22 | 23 | 50 => ObjParam::Color(Color { id: parse_id(val)? }),
+        80 => match obj_id {
+            // collision trigger and collision block
+            Some("1815") | Some("1816") => ObjParam::Block(Block { id: parse_id(val)? }),
+            _ => ObjParam::Item(Item { id: parse_id(val)? }),
+        },
+        95 => ObjParam::Block(Block { id: parse_id(val)? }),
+        // text and hsv strings
+        31 | 43 | 44 | 49 => ObjParam::Text(val.to_string()),
+        k if BOOL_KEYS.contains(&k) => ObjParam::Bool(val == "1"),
+        _ => match val.parse::<f64>() {
+            Ok(n) => ObjParam::Number(n),
+            Err(_) => ObjParam::Text(val.to_string()),
+        },
+    })
+}
+
+// parses a single object in the "key,value,key,value" format
+pub fn parse_obj(obj: &str) -> Result<GdObj, String> {
+    // objects serialized by spwn end in a trailing comma
+    let key_val: Vec<&str> = obj.trim_end_matches(',').split(',').collect();
+    if !key_val.len().is_multiple_of(2) {
+        return Err(format!("Object has a key without a value: \"{}\"", obj));
+    }
+    let mut props = HashMap::<u16, &str>::new();
+    for i in (0..key_val.len()).step_by(2) {
+        match key_val[i].parse::<u16>() {
+            Ok(key) => props.insert(key, key_val[i + 1]),
+            Err(_) => return Err(format!("Invalid object key: \"{}\"", key_val[i])),
+        };
+    }
+
+    let mut params = HashMap::new();
+    for (key, val) in &props {
+        params.insert(*key, parse_param(*key, val, &props)?);
+    }
+
+    let mode = match params.get(&1) {
+        Some(ObjParam::Number(n))
+            if TRIGGER_IDS.contains(&(*n as u16)) || GD22_TRIGGER_IDS.contains(&(*n as u16)) =>
+        {
+            ObjectMode::Trigger
+        }
+        _ => ObjectMode::Object,
+    };
+
+    Ok(GdObj {
+        func_id: 0,
+        params,
+        mode,
+        unique_id: 0,
+        sync_group: 0,
+        sync_part: 0,
+        source: None,
+    })
+}
+
+// splits a level string into its header (start settings, color channels etc.)
+// and the objects in it
+pub fn parse_level_string(ls: &str) -> Result<(String, Vec<GdObj>), String> {
+    let mut header = String::new();
+    let mut objects = Vec::new();
+    for (i, obj) in ls.split(';').enumerate() {
+        if obj.is_empty() {
+            continue;
+        }
+        if i == 0 && obj.starts_with('k') {
+            header = obj.to_string();
+            continue;
+        }
+        objects.push(parse_obj(obj)?);
+    }
+    Ok((header, objects))
 }
 
-pub fn get_used_ids(ls: &str) -> [HashSet<u16>; 4] {
+// an object that can't be read is an error, since the ids in it would otherwise be reused
+pub fn get_used_ids(ls: &str) -> Result<[HashSet<u16>; 4], String> {
     let mut out = [
         HashSet::<u16>::new(),
         HashSet::<u16>::new(),
         HashSet::<u16>::new(),
         HashSet::<u16>::new(),
     ];
-    let objects = ls.split(';');
-    for obj in objects {
-        let props: Vec<&str> = obj.split(',').collect();
-        let mut map = HashMap::new();
-
-        for i in (0..props.len() - 1).step_by(2) {
-            map.insert(props[i], props[i + 1]);
+    for (i, obj) in ls.split(';').enumerate() {
+        if obj.is_empty() || (i == 0 && obj.starts_with('k')) {
+            continue;
         }
-
-        for (key, value) in &map {
-            match *key {
-                "57" => {
-                    //GROUPS
-                    let groups = value.split('.');
-                    for g in groups {
-                        let group = g.parse().unwrap();
-
-                        out[0].insert(group);
+        let obj = match parse_obj(obj) {
+            Ok(o) => o,
+            Err(e) => return Err(format!("Could not read an object in the level: {}", e)),
+        };
+        //counter display => do nothing
+        let counter_display = obj.params.get(&1) == Some(&ObjParam::Number(1615.0));
+
+        for param in obj.params.values() {
+            let (class_index, ids) = match param {
+                ObjParam::Group(g) => (0, vec![g.id]),
+                ObjParam::GroupList(l) => (0, l.iter().map(|g| g.id).collect()),
+                ObjParam::Color(c) => (1, vec![c.id]),
+                ObjParam::Block(b) => (2, vec![b.id]),
+                ObjParam::Item(i) if !counter_display => (3, vec![i.id]),
+                _ => continue,
+            };
+            for id in ids {
+                // 0 means "no id"
+                if let Id::Specific(n) = id {
+                    if n != 0 {
+                        out[class_index].insert(n);
                     }
                 }
-                "51" => {
-                    match (map.get("1"), map.get("52")) {
-                        (Some(&"1006"), Some(&"1")) => out[0].insert(value.parse().unwrap()),
-                        (Some(&"1006"), _) => out[1].insert(value.parse().unwrap()),
-                        _ => out[0].insert(value.parse().unwrap()),
-                    };
-                }
-                "71" => {
-                    out[0].insert(value.parse().unwrap());
-                }
-                //colors
-                "21" => {
-                    out[1].insert(value.parse().unwrap());
-                }
-                "22" => {
-                    out[1].insert(value.parse().unwrap());
-                }
-                "23" => {
-                    out[1].insert(value.parse().unwrap());
-                }
-
-                "80" => {
-                    match map.get("1") {
-                        //if collision trigger, add block id
-                        Some(&"1815") => out[2].insert(value.parse().unwrap()),
-                        //counter display => do nothing
-                        Some(&"1615") => false,
-                        // else add item id
-                        _ => out[3].insert(value.parse().unwrap()),
-                    };
-                }
-
-                "95" => {
-                    out[2].insert(value.parse().unwrap());
-                }
-                //some of these depends on what object it is
-                //pulse target depends on group mode/color mode
-                //figure this out, future me
-                _ => (),
             }
         }
     }
-    out
+    Ok(out)
 }
 
 const START_HEIGHT: u16 = 10;
@@ -158,41 +296,204 @@ pub const SPWN_SIGNATURE_GROUP: Group = Group {
 };
 //use crate::ast::ObjectMode;
 
-pub fn remove_spwn_objects(file_content: &mut String) {
+// the signature group is inside the 2.2 group range, so it's never given to arbitrary groups
+fn is_spwn_id(class_index: usize, id: u16) -> bool {
+    class_index == 0 && Id::Specific(id) == SPWN_SIGNATURE_GROUP.id
+}
+
+const NAMESPACE_MARKER: &str = "SPWN namespace: ";
+
+// a text object (out of sight, left of the start) that records which group
+// the objects of a namespace have, since it gets the same groups as them
+fn namespace_marker(namespace: &str) -> GdObj {
+    let mut params = HashMap::new();
+    params.insert(1, ObjParam::Number(914.0));
+    params.insert(2, ObjParam::Number(-105.0));
+    params.insert(3, ObjParam::Number(105.0));
+    params.insert(
+        31,
+        ObjParam::Text(base64::encode(
+            format!("{}{}", NAMESPACE_MARKER, namespace).as_bytes(),
+        )),
+    );
+    params.insert(32, ObjParam::Number(0.5));
+    GdObj {
+        func_id: 0,
+        params,
+        mode: ObjectMode::Object,
+        unique_id: 0,
+        sync_group: 0,
+        sync_part: 0,
+        source: None,
+    }
+}
+
+// the namespaces that have objects in a level, and their groups
+pub fn level_namespaces(ls: &str) -> Result<HashMap<String, u16>, String> {
+    let (_, objects) = parse_level_string(ls)?;
+    let mut out = HashMap::new();
+    for obj in objects {
+        if !obj.has_group(SPWN_SIGNATURE_GROUP) {
+            continue;
+        }
+        let name = match obj.params.get(&31) {
+            Some(ObjParam::Text(t)) => match base64::decode(t)
+                .ok()
+                .and_then(|t| String::from_utf8(t).ok())
+            {
+                Some(text) => match text.strip_prefix(NAMESPACE_MARKER) {
+                    Some(name) => name.to_string(),
+                    None => continue,
+                },
+                None => continue,
+            },
+            _ => continue,
+        };
+        let group = match obj.params.get(&57) {
+            Some(ObjParam::GroupList(l)) => l.iter().find_map(|g| match g.id {
+                Id::Specific(n) if *g != SPWN_SIGNATURE_GROUP => Some(n),
+                _ => None,
+            }),
+            _ => None,
+        };
+        if let Some(group) = group {
+            out.insert(name, group);
+        }
+    }
+    Ok(out)
+}
+
+// removes the objects added by SPWN, or only the ones from one namespace
+// (and the ones from builds that had no namespace). returns the amount of objects removed
+pub fn remove_spwn_objects(
+    file_content: &mut String,
+    namespace: Option<&str>,
+) -> Result<usize, String> {
     let spwn_group = match SPWN_SIGNATURE_GROUP.id {
-        Id::Specific(n) => n.to_string(),
+        Id::Specific(n) => n,
         _ => unreachable!(),
     };
+    let namespaces = level_namespaces(file_content)?;
+    let own_group = namespace.and_then(|n| namespaces.get(n).copied());
+
+    let mut removed = 0;
     (*file_content) = file_content
         //remove previous spwn objects
         .split(';')
-        .map(|obj| {
+        .filter(|obj| {
             let key_val: Vec<&str> = obj.split(',').collect();
-            let mut ret = obj;
             for i in (0..key_val.len()).step_by(2) {
-                if key_val[i] == "57" {
-                    let mut groups = key_val[i + 1].split('.');
-                    if groups.any(|x| x == spwn_group) {
-                        ret = "";
+                if key_val[i] == "57" && i + 1 < key_val.len() {
+                    let groups = key_val[i + 1]
+                        .split('.')
+                        .filter_map(|g| g.parse::<u16>().ok())
+                        .collect::<Vec<u16>>();
+                    if !groups.contains(&spwn_group) {
+                        continue;
+                    }
+                    let remove = match namespace {
+                        Some(_) => match own_group {
+                            Some(g) if groups.contains(&g) => true,
+                            _ => !groups.iter().any(|g| namespaces.values().any(|n| n == g)),
+                        },
+                        None => true,
+                    };
+                    if remove {
+                        removed += 1;
+                        return false;
                     }
                 }
             }
-            ret
+            true
         })
         .collect::<Vec<&str>>()
         .join(";");
+    Ok(removed)
+}
+
+const ID_CLASS_SUFFIXES: [char; 4] = ['g', 'c', 'b', 'i'];
+
+// id ranges that arbitrary ids never get, and that the script can't use
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReservedIds {
+    // start and end (exclusive) of each range, for each id class
+    pub ranges: [Vec<(u16, u16)>; 4],
+}
+
+impl ReservedIds {
+    // adds comma separated ranges like "100..200g, 5c"
+    pub fn add(&mut self, ranges: &str) -> Result<(), String> {
+        for range in ranges
+            .split(',')
+            .map(|r| r.trim())
+            .filter(|r| !r.is_empty())
+        {
+            let err = || {
+                format!(
+                    "Invalid reserved range \"{}\" (expected something like 100..200g or 5c)",
+                    range
+                )
+            };
+            let class_index = match range
+                .chars()
+                .last()
+                .and_then(|c| ID_CLASS_SUFFIXES.iter().position(|s| *s == c))
+            {
+                Some(i) => i,
+                None => return Err(err()),
+            };
+            let numbers = &range[..range.len() - 1];
+            let (start, end) = match numbers.find("..") {
+                Some(i) => (
+                    numbers[..i].parse::<u16>().ok(),
+                    numbers[i + 2..].parse::<u16>().ok(),
+                ),
+                None => {
+                    let id = numbers.parse::<u16>().ok();
+                    (id, id.and_then(|n| n.checked_add(1)))
+                }
+            };
+            match (start, end) {
+                (Some(start), Some(end)) if start < end => {
+                    self.ranges[class_index].push((start, end))
+                }
+                _ => return Err(err()),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn find(&self, class_index: usize, id: u16) -> Option<String> {
+        self.ranges[class_index]
+            .iter()
+            .find(|(start, end)| id >= *start && id < *end)
+            .map(|(start, end)| {
+                if *end == start + 1 {
+                    format!("{}{}", start, ID_CLASS_SUFFIXES[class_index])
+                } else {
+                    format!("{}..{}{}", start, end, ID_CLASS_SUFFIXES[class_index])
+                }
+            })
+    }
 }
 
-//returns the string to be appended to the old string
+//returns the string to be appended to the old string, and the ids the arbitrary ids got
+// with a namespace, the objects also get the lowest free group, which a marker object records
 pub fn append_objects(
     mut objects: Vec<GdObj>,
     old_ls: &str,
-) -> Result<(String, [usize; 4]), String> {
-    let mut closed_ids = get_used_ids(&old_ls);
+    namespace: Option<&str>,
+    preferred_ids: &IdMaps,
+    reserved: &ReservedIds,
+    target: &Target,
+) -> Result<(String, [usize; 4], IdMaps), String> {
+    let mut closed_ids = get_used_ids(old_ls)?;
 
     //collect all specific ids mentioned into closed_[id] lists
     for obj in &objects {
-        for prop in obj.params.values() {
+        let mut props = obj.params.iter().collect::<Vec<(&u16, &ObjParam)>>();
+        props.sort_by_key(|(k, _)| **k);
+        for (_, prop) in props {
             let class_index;
             let id;
             match prop {
@@ -223,6 +524,15 @@ pub fn append_objects(
             for id in id {
                 match id {
                     Id::Specific(i) => {
+                        // 0 means "no id"
+                        if i != 0 {
+                            if let Some(range) = reserved.find(class_index, i) {
+                                return Err(format!(
+                                    "The script uses {}{}, which is in the reserved range {}",
+                                    i, ID_CLASS_SUFFIXES[class_index], range
+                                ));
+                            }
+                        }
                         closed_ids[class_index].insert(i);
                     }
                     _ => continue,
@@ -232,17 +542,47 @@ pub fn append_objects(
     }
 
     //find new ids for all the arbitrary ones
-    let mut id_maps: [HashMap<ArbitraryId, SpecificId>; 4] = [
-        HashMap::new(),
-        HashMap::new(),
-        HashMap::new(),
-        HashMap::new(),
-    ];
-
-    const ID_MAX: u16 = 999;
+    let mut id_maps: IdMaps = Default::default();
+
+    // ids from the lockfile are kept for their arbitrary ids, if they are still free
+    let mut preferred_ids = preferred_ids.clone();
+    for (i, ids) in preferred_ids.iter_mut().enumerate() {
+        ids.retain(|_, id| {
+            !closed_ids[i].contains(id) && reserved.find(i, *id).is_none() && !is_spwn_id(i, *id)
+        });
+    }
+    let reserved_ids = preferred_ids
+        .iter()
+        .map(|ids| ids.values().copied().collect::<HashSet<SpecificId>>())
+        .collect::<Vec<HashSet<SpecificId>>>();
+
+    let mut signature = vec![SPWN_SIGNATURE_GROUP];
+    if let Some(namespace) = namespace {
+        let group = match (1..=target.id_limits[0]).find(|i| {
+            !closed_ids[0].contains(i)
+                && !reserved_ids[0].contains(i)
+                && reserved.find(0, *i).is_none()
+                && !is_spwn_id(0, *i)
+        }) {
+            Some(i) => i,
+            None => return Err("This level exceeds the group limit!".to_string()),
+        };
+        closed_ids[0].insert(group);
+        let group = Group {
+            id: Id::Specific(group),
+        };
+        objects.push(namespace_marker(namespace));
+        signature.push(group);
+    }
 
     for obj in &mut objects {
-        for prop in obj.params.values_mut() {
+        // in order of the keys, so the same objects always get the same ids
+        let mut props = obj
+            .params
+            .iter_mut()
+            .collect::<Vec<(&u16, &mut ObjParam)>>();
+        props.sort_by_key(|(k, _)| **k);
+        for (_, prop) in props {
             let class_index;
             let ids: Vec<&mut Id>;
             match prop {
@@ -274,15 +614,26 @@ pub fn append_objects(
                         *id = Id::Specific(match id_maps[class_index].get(i) {
                             Some(a) => *a,
                             None => {
-                                let mut out = None;
-                                for i in 1..10000 {
-                                    if !closed_ids[class_index].contains(&i) {
-                                        out = Some(i);
-                                        closed_ids[class_index].insert(i);
-                                        break;
+                                // the preferred id can already be taken if the lockfile
+                                // gives more than one origin the same id
+                                let mut out = preferred_ids[class_index]
+                                    .get(i)
+                                    .copied()
+                                    .filter(|id| !closed_ids[class_index].contains(id));
+                                if out.is_none() {
+                                    for i in 1..=target.id_limits[class_index] {
+                                        if !closed_ids[class_index].contains(&i)
+                                            && !reserved_ids[class_index].contains(&i)
+                                            && reserved.find(class_index, i).is_none()
+                                            && !is_spwn_id(class_index, i)
+                                        {
+                                            out = Some(i);
+                                            break;
+                                        }
                                     }
                                 }
                                 if let Some(id) = out {
+                                    closed_ids[class_index].insert(id);
                                     id_maps[class_index].insert(*i, id);
                                     id
                                 } else {
@@ -300,37 +651,41 @@ pub fn append_objects(
         }
     }
     for (i, list) in closed_ids.iter().enumerate() {
-        if list.len() > ID_MAX as usize {
+        // the signature group doesn't take up an id that could be used
+        let used = list.iter().filter(|id| !is_spwn_id(i, **id)).count();
+        if used > target.id_limits[i] as usize {
             return Err(format!(
                 "This level exceeds the {} limit! ({}/{})",
                 ["group", "color", "block ID", "item ID"][i],
-                list.len(),
-                ID_MAX
+                used,
+                target.id_limits[i]
             ));
         }
     }
 
     //println!("group_map: {:?}", id_maps[0]);
 
-    fn serialize_obj(mut trigger: GdObj) -> String {
+    fn serialize_obj(mut trigger: GdObj, signature: &[Group]) -> String {
         let mut obj_string = String::new();
+        match trigger.params.get_mut(&57) {
+            Some(ObjParam::GroupList(l)) => (*l).extend_from_slice(signature),
+            Some(ObjParam::Group(g)) => {
+                let mut groups = vec![*g];
+                groups.extend_from_slice(signature);
+                trigger.params.insert(57, ObjParam::GroupList(groups));
+            }
+            _ => {
+                trigger.params.insert(
+                    57,
+                    match signature {
+                        [group] => ObjParam::Group(*group),
+                        _ => ObjParam::GroupList(signature.to_vec()),
+                    },
+                );
+            }
+        };
         match trigger.mode {
             ObjectMode::Object => {
-                match trigger.params.get_mut(&57) {
-                    Some(ObjParam::GroupList(l)) => (*l).push(SPWN_SIGNATURE_GROUP),
-                    Some(ObjParam::Group(g)) => {
-                        let group = *g;
-                        trigger
-                            .params
-                            .insert(57, ObjParam::GroupList(vec![group, SPWN_SIGNATURE_GROUP]));
-                    }
-                    _ => {
-                        trigger
-                            .params
-                            .insert(57, ObjParam::Group(SPWN_SIGNATURE_GROUP));
-                    }
-                };
-
                 let mut param_list = trigger.params.iter().collect::<Vec<(&u16, &ObjParam)>>();
 
                 param_list.sort_by(|a, b| (*a.0).cmp(b.0));
@@ -342,25 +697,6 @@ pub fn append_objects(
                 obj_string + ";"
             }
             ObjectMode::Trigger => {
-                match trigger.params.get_mut(&57) {
-                    Some(ObjParam::GroupList(l)) => {
-                        (*l).push(SPWN_SIGNATURE_GROUP);
-                        //list
-                    }
-                    Some(ObjParam::Group(g)) => {
-                        let group = *g;
-                        trigger
-                            .params
-                            .insert(57, ObjParam::GroupList(vec![group, SPWN_SIGNATURE_GROUP]));
-                    }
-                    _ => {
-                        trigger
-                            .params
-                            .insert(57, ObjParam::Group(SPWN_SIGNATURE_GROUP));
-                        //Vec::new()
-                    }
-                };
-
                 /*let spawned = match trigger.params.get(&62) {
                     Some(ObjParam::Bool(b)) => *b,
                     _ => groups.iter().any(|x| x.id != ID::Specific(0)),
@@ -385,7 +721,7 @@ pub fn append_objects(
     let mut full_obj_string = String::new();
 
     for obj in objects {
-        full_obj_string += &serialize_obj(obj)
+        full_obj_string += &serialize_obj(obj, &signature)
     }
     Ok((
         full_obj_string,
@@ -395,110 +731,353 @@ pub fn append_objects(
             closed_ids[2].len(),
             closed_ids[3].len(),
         ],
+        id_maps,
     ))
 }
 
-pub fn apply_fn_ids(func_ids: &[FunctionId]) -> Vec<GdObj> {
-    //println!("{:?}", trigger);
+// how the generated triggers are placed in the editor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayoutStrategy {
+    // a column (or more) for every function, followed by the functions made inside it
+    Columns,
+    // every trigger in one grid that's about as wide as it is tall
+    Grid,
+    // the functions of each file together, with some space between the files
+    Modules,
+}
 
-    fn apply_fn_id(
-        id_index: usize,
-        func_ids: &[FunctionId],
-        x_offset: u32,
-        y_offset: u16,
-    ) -> (Vec<GdObj>, u32) {
-        let id = func_ids[id_index].clone();
+pub const LAYOUT_NAMES: &[&str] = &["columns", "grid", "modules"];
+
+// where the first column of triggers starts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayoutOrigin {
+    // near x = 0, where the triggers have always been
+    Default,
+    // the top of the first column, in editor units (30 per block)
+    At(f64, f64),
+    // to the right of every object in the level
+    AfterLevel,
+}
 
-        let mut objects = Vec::<GdObj>::new();
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    pub strategy: LayoutStrategy,
+    pub origin: LayoutOrigin,
+    // the editor layer the triggers are put on
+    pub editor_layer: Option<u16>,
+    // text objects above the triggers, saying what made them
+    pub debug_labels: bool,
+}
 
-        let mut current_x = 0;
-        /*if !id.obj_list.is_empty() {
-            //add label
-            obj_string += &format!(
-                "1,914,2,{},3,{},31,{},32,0.5;",
-                x_offset * 30 + 15,
-                ((81 - START_HEIGHT) - y_offset) * 30 + 15,
-                base64::encode(id.name.as_bytes())
-            );
-        }*/
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            strategy: LayoutStrategy::Columns,
+            origin: LayoutOrigin::Default,
+            editor_layer: None,
+            debug_labels: false,
+        }
+    }
+}
 
-        //add top layer
-        let possible_height = MAX_HEIGHT - (START_HEIGHT + y_offset); //30 is max (TODO: case for if y_offset is more than 30)
+impl Layout {
+    pub fn parse_strategy(name: &str) -> Result<LayoutStrategy, String> {
+        match name {
+            "columns" => Ok(LayoutStrategy::Columns),
+            "grid" => Ok(LayoutStrategy::Grid),
+            "modules" => Ok(LayoutStrategy::Modules),
+            a => Err(format!(
+                "Unknown layout {} (expected one of: {})",
+                a,
+                LAYOUT_NAMES.join(", ")
+            )),
+        }
+    }
 
-        for (i, (obj, _)) in id.obj_list.iter().enumerate() {
-            match obj.mode {
-                ObjectMode::Object => {
-                    objects.push(obj.clone());
-                }
-                ObjectMode::Trigger => {
-                    let y_pos = (i as u16) % possible_height + START_HEIGHT + y_offset;
-                    let x_pos = (i as f64 / possible_height as f64).floor() as u32 + x_offset;
-
-                    let spawned = match obj.params.get(&62) {
-                        Some(ObjParam::Bool(b)) => *b,
-                        _ => match obj.params.get(&57) {
-                            None => false,
-                            // Some(ObjParam::GroupList(l)) => {
-                            //     l.iter().any(|x| x.id != ID::Specific(0))
-                            // }
-                            Some(ObjParam::Group(g)) => g.id != Id::Specific(0),
-                            _ => unreachable!(),
-                        },
-                    };
+    // "after-level", or a position like "-3000,-30"
+    pub fn parse_origin(arg: &str) -> Result<LayoutOrigin, String> {
+        if arg == "after-level" {
+            return Ok(LayoutOrigin::AfterLevel);
+        }
+        let pos = arg
+            .split(',')
+            .map(|n| n.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>();
+        match pos.as_deref() {
+            Ok([x, y]) => Ok(LayoutOrigin::At(*x, *y)),
+            _ => Err(format!(
+                "Expected a position like -3000,-30 or after-level, found {}",
+                arg
+            )),
+        }
+    }
+}
 
-                    let mut new_obj = obj.clone();
+fn obj_pos(obj: &GdObj) -> Option<(f64, f64)> {
+    match (obj.params.get(&2), obj.params.get(&3)) {
+        (Some(ObjParam::Number(x)), Some(ObjParam::Number(y))) => Some((*x, *y)),
+        _ => None,
+    }
+}
 
-                    if spawned {
-                        new_obj.params.insert(62, ObjParam::Bool(true));
-                        new_obj.params.insert(87, ObjParam::Bool(true));
-                    }
+fn grid_cell((x, y): (f64, f64)) -> (i64, i64) {
+    ((x / 30.0).floor() as i64, (y / 30.0).floor() as i64)
+}
 
-                    new_obj.params.insert(
-                        2,
-                        if spawned {
-                            ObjParam::Number((x_pos * 30 + 15) as f64)
-                        } else {
-                            ObjParam::Number(0.0)
-                        },
-                    );
-                    new_obj
-                        .params
-                        .insert(3, ObjParam::Number(((80 - y_pos) * 30 + 15) as f64));
-                    objects.push(new_obj);
-                }
+fn is_spawned(obj: &GdObj) -> bool {
+    match obj.params.get(&62) {
+        Some(ObjParam::Bool(b)) => *b,
+        _ => match obj.params.get(&57) {
+            None => false,
+            // Some(ObjParam::GroupList(l)) => {
+            //     l.iter().any(|x| x.id != ID::Specific(0))
+            // }
+            Some(ObjParam::Group(g)) => g.id != Id::Specific(0),
+            _ => unreachable!(),
+        },
+    }
+}
+
+// hands out spots for triggers column by column, skipping the ones that are taken
+struct Placer {
+    x: f64,
+    top: f64,
+    height: u16,
+    row: u16,
+    taken: HashSet<(i64, i64)>,
+    // triggers that aren't spawned run when the player passes them,
+    // so they all go at x = 0. these are the spots left for them, last one first
+    start: Vec<f64>,
+}
+
+impl Placer {
+    // finds the spots for the triggers that aren't spawned before anything else is placed,
+    // so the spawned ones don't end up on top of them
+    fn reserve_start(&mut self, count: usize) {
+        let mut y = self.top;
+        while self.start.len() < count {
+            if self.taken.insert(grid_cell((0.0, y))) {
+                self.start.push(y);
             }
+            y -= 30.0;
         }
-        if !id.obj_list.is_empty() {
-            current_x += (id.obj_list.len() as f64 / possible_height as f64).floor() as u32 + 1;
+        self.start.reverse();
+    }
+
+    fn next_start(&mut self) -> f64 {
+        self.start.pop().unwrap()
+    }
+
+    fn next(&mut self) -> (f64, f64) {
+        loop {
+            if self.row >= self.height {
+                self.next_column();
+            }
+            let pos = (self.x, self.top - self.row as f64 * 30.0);
+            self.row += 1;
+            if !self.taken.contains(&grid_cell(pos)) {
+                return pos;
+            }
         }
+    }
 
-        //add all children
-        for (i, func_id) in func_ids.iter().enumerate() {
-            if func_id.parent == Some(id_index) {
-                let (obj, new_length) = apply_fn_id(i, func_ids, current_x + x_offset, y_offset);
-                objects.extend(obj);
+    // starts a new column, unless nothing is in the current one
+    fn next_column(&mut self) {
+        if self.row > 0 {
+            self.x += 30.0;
+            self.row = 0;
+        }
+    }
 
-                if new_length > 0 {
-                    current_x += new_length + 1;
+    fn gap(&mut self, columns: u16) {
+        self.next_column();
+        self.x += columns as f64 * 30.0;
+    }
+}
+
+// positions the triggers of a function, and returns the number of spawned triggers in it
+fn place_fn_id(
+    func_ids: &[FunctionId],
+    func_id_index: usize,
+    layout: &Layout,
+    placer: &mut Placer,
+    objects: &mut Vec<GdObj>,
+) -> usize {
+    let func_id = &func_ids[func_id_index];
+    let mut triggers = 0;
+    let mut first = None;
+    for (obj, _) in &func_id.obj_list {
+        match obj.mode {
+            ObjectMode::Object => {
+                objects.push(obj.clone());
+            }
+            ObjectMode::Trigger => {
+                let mut new_obj = obj.clone();
+
+                let (x, y) = if is_spawned(obj) {
+                    new_obj.params.insert(62, ObjParam::Bool(true));
+                    new_obj.params.insert(87, ObjParam::Bool(true));
+                    triggers += 1;
+                    let pos = placer.next();
+                    first = first.or(Some(pos));
+                    pos
+                } else {
+                    (0.0, placer.next_start())
+                };
+                new_obj.params.insert(2, ObjParam::Number(x));
+                new_obj.params.insert(3, ObjParam::Number(y));
+                if let Some(layer) = layout.editor_layer {
+                    new_obj.params.insert(20, ObjParam::Number(layer as f64));
                 }
+                objects.push(new_obj);
             }
         }
-
-        (objects, current_x)
     }
 
-    let mut full_obj_list = Vec::<GdObj>::new();
+    //add label
+    let label = match (&func_id.name, &func_id.site) {
+        (Some(name), Some(site)) => Some(format!("{} {}", name, site)),
+        (Some(name), None) => Some(name.clone()),
+        (None, Some(site)) => Some(site.clone()),
+        (None, None) => None,
+    };
+    if let (true, Some((x, _)), Some(label)) = (layout.debug_labels, first, label) {
+        // above the column, or higher up if something is in the way
+        let mut y = placer.top + 30.0;
+        while placer.taken.contains(&grid_cell((x, y))) {
+            y += 30.0;
+        }
+        placer.taken.insert(grid_cell((x, y)));
+
+        let mut params = HashMap::new();
+        params.insert(1, ObjParam::Number(914.0));
+        params.insert(2, ObjParam::Number(x));
+        params.insert(3, ObjParam::Number(y));
+        params.insert(31, ObjParam::Text(base64::encode(label.as_bytes())));
+        params.insert(32, ObjParam::Number(0.5));
+        if let Some(layer) = layout.editor_layer {
+            params.insert(20, ObjParam::Number(layer as f64));
+        }
+        objects.push(GdObj {
+            func_id: func_id_index,
+            params,
+            mode: ObjectMode::Object,
+            unique_id: 0,
+            sync_group: 0,
+            sync_part: 0,
+            source: None,
+        });
+    }
+    triggers
+}
 
-    let mut current_x = 0;
+// the function ids in the order they are laid out: every function, then the ones made inside it
+fn fn_id_order(func_ids: &[FunctionId]) -> Vec<usize> {
+    let mut children = vec![Vec::new(); func_ids.len()];
+    let mut roots = Vec::new();
     for (i, func_id) in func_ids.iter().enumerate() {
-        if func_id.parent == None {
-            let (objects, new_length) = apply_fn_id(i, &func_ids, current_x, 0);
-            full_obj_list.extend(objects);
+        match func_id.parent {
+            Some(p) => children[p].push(i),
+            None => roots.push(i),
+        }
+    }
+    let mut order = Vec::new();
+    let mut stack = roots.into_iter().rev().collect::<Vec<usize>>();
+    while let Some(i) = stack.pop() {
+        order.push(i);
+        stack.extend(children[i].iter().rev());
+    }
+    order
+}
 
-            current_x += new_length;
+// places all triggers in the editor, avoiding the objects already in the level
+pub fn apply_fn_ids(
+    func_ids: &[FunctionId],
+    layout: &Layout,
+    level_objects: &[GdObj],
+) -> Vec<GdObj> {
+    let (spawned, start): (Vec<&GdObj>, Vec<&GdObj>) = func_ids
+        .iter()
+        .flat_map(|f| f.obj_list.iter().map(|(o, _)| o))
+        .filter(|o| o.mode == ObjectMode::Trigger)
+        .partition(|o| is_spawned(o));
+
+    let default_top = ((80 - START_HEIGHT) * 30 + 15) as f64;
+    let (x, top) = match layout.origin {
+        LayoutOrigin::Default => (15.0, default_top),
+        LayoutOrigin::At(x, y) => (x, y),
+        LayoutOrigin::AfterLevel => {
+            let right = level_objects
+                .iter()
+                .filter_map(obj_pos)
+                .map(|(x, _)| x)
+                .fold(None, |max: Option<f64>, x| {
+                    Some(max.map_or(x, |m| m.max(x)))
+                });
+            match right {
+                Some(right) => (((right / 30.0).floor() + 2.0) * 30.0 + 15.0, default_top),
+                None => (15.0, default_top),
+            }
+        }
+    };
+    let column_height = MAX_HEIGHT - START_HEIGHT;
+    let height = match layout.strategy {
+        LayoutStrategy::Grid => ((spawned.len() as f64).sqrt().ceil() as u16)
+            .max(1)
+            .min(column_height),
+        _ => column_height,
+    };
+    let taken = level_objects
+        .iter()
+        .filter_map(obj_pos)
+        .map(grid_cell)
+        .collect::<HashSet<(i64, i64)>>();
+    let mut placer = Placer {
+        x,
+        top,
+        height,
+        row: 0,
+        taken,
+        start: Vec::new(),
+    };
+    placer.reserve_start(start.len());
+
+    let mut objects = Vec::<GdObj>::new();
+    let order = fn_id_order(func_ids);
+    match layout.strategy {
+        LayoutStrategy::Columns => {
+            for i in &order {
+                place_fn_id(func_ids, *i, layout, &mut placer, &mut objects);
+                placer.next_column();
+            }
+        }
+        LayoutStrategy::Grid => {
+            for i in &order {
+                place_fn_id(func_ids, *i, layout, &mut placer, &mut objects);
+            }
+        }
+        LayoutStrategy::Modules => {
+            let mut files = Vec::<(&Option<PathBuf>, Vec<usize>)>::new();
+            for i in order {
+                let file = &func_ids[i].file;
+                match files.iter_mut().find(|(f, _)| *f == file) {
+                    Some((_, list)) => list.push(i),
+                    None => files.push((file, vec![i])),
+                }
+            }
+            for (_, list) in files {
+                let mut placed = 0;
+                for i in list {
+                    placed += place_fn_id(func_ids, i, layout, &mut placer, &mut objects);
+                    placer.next_column();
+                }
+                if placed > 0 {
+                    placer.gap(3);
+                }
+            }
         }
     }
-    full_obj_list
+    objects
 }
 /* PYTHON CODE IM USING
 def Xor(data,key):
@@ -664,12 +1243,11 @@ fn xor(data: Vec<u8>, key: u8) -> Vec<u8> {
     }
     new_data
 }
-fn base_64_decrypt(encoded: Vec<u8>) -> Vec<u8> {
-    let mut new_data = encoded;
-    while new_data.len() % 4 != 0 {
-        new_data.push(b'=')
+fn base_64_pad(mut encoded: String) -> String {
+    while !encoded.len().is_multiple_of(4) {
+        encoded.push('=')
     }
-    base64::decode(String::from_utf8(new_data).unwrap().as_str()).unwrap()
+    encoded
 }
 
 use quick_xml::events::{BytesText, Event};
@@ -691,7 +1269,7 @@ fn decrypt_savefile(mut sf: Vec<u8>) -> Result<Vec<u8>, String> {
         type AesEcb = Ecb<Aes256, Pkcs7>;
 
         // re-create cipher mode instance
-        let cipher = AesEcb::new_var(&IOS_KEY, &[]).unwrap();
+        let cipher = AesEcb::new_var(IOS_KEY, &[]).unwrap();
 
         Ok(match cipher.decrypt(&mut sf) {
             Ok(v) => v,
@@ -799,22 +1377,110 @@ pub fn get_level_string(ls: Vec<u8>, level_name: Option<String>) -> Result<Strin
         }
     }*/
     //decrypting level string
-    let ls_b64 = base_64_decrypt(
-        level_string
+    decode_level_string(&level_string)
+}
+
+// inverse of encode_level_string
+pub fn decode_level_string(encoded: &str) -> Result<String, String> {
+    let ls_b64 = match base64::decode(&base_64_pad(
+        encoded
+            .trim()
             .replace("-", "+")
             .replace("_", "/")
-            .replace("\0", "")
-            .as_bytes()
-            .to_vec(),
-    );
-
-    //println!("{}", String::from_utf8(ls_b64.clone()).unwrap());
+            .replace("\0", ""),
+    )) {
+        Ok(b) => b,
+        Err(e) => return Err(format!("Invalid level string: {}", e)),
+    };
 
-    let mut ls_decoder = gzip::Decoder::new(&ls_b64[..]).unwrap();
     let mut ls_buf = Vec::new();
-    ls_decoder.read_to_end(&mut ls_buf).unwrap();
+    match gzip::Decoder::new(&ls_b64[..]) {
+        Ok(mut ls_decoder) => {
+            if let Err(e) = ls_decoder.read_to_end(&mut ls_buf) {
+                return Err(format!("Invalid level string: {}", e));
+            }
+        }
+        Err(e) => return Err(format!("Invalid level string: {}", e)),
+    };
+
+    Ok(String::from_utf8_lossy(&ls_buf).to_string())
+}
+
+// reads the level string out of a .gmd file (a single exported level)
+pub fn get_gmd_level_string(gmd: &str) -> Result<String, String> {
+    let mut reader = Reader::from_str(gmd);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut k4_detected = false;
+
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Text(e)) => {
+                let text = e.unescape_and_decode(&reader).unwrap();
+                if k4_detected {
+                    return decode_level_string(&text);
+                }
+                k4_detected = text == "k4";
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                return Err(format!(
+                    "Error at position {}: {:?}",
+                    reader.buffer_position(),
+                    e
+                ))
+            }
+            _ => (),
+        }
+        buf.clear();
+    }
+    Err("No level string found in the .gmd file".to_string())
+}
+
+// replaces the level string in the contents of a .gmd file
+pub fn set_gmd_level_string(gmd: &str, ls: &str) -> Result<String, String> {
+    let mut reader = Reader::from_str(gmd);
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+
+    let mut buf = Vec::new();
+    let mut k4_detected = false;
+    let mut found = false;
 
-    Ok(String::from_utf8(ls_buf).unwrap())
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Text(e)) => {
+                let text = e.unescape_and_decode(&reader).unwrap();
+                if text.trim().is_empty() {
+                    assert!(writer.write_event(Event::Text(e)).is_ok());
+                } else if k4_detected && !found {
+                    found = true;
+                    let encoded = encode_level_string(ls);
+                    assert!(writer
+                        .write_event(Event::Text(BytesText::from_plain_str(&encoded)))
+                        .is_ok());
+                } else {
+                    k4_detected = text == "k4";
+                    assert!(writer.write_event(Event::Text(e)).is_ok());
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                return Err(format!(
+                    "Error at position {}: {:?}",
+                    reader.buffer_position(),
+                    e
+                ))
+            }
+            Ok(e) => assert!(writer.write_event(e).is_ok()),
+        }
+        buf.clear();
+    }
+
+    if !found {
+        return Err("No level string found in the .gmd file".to_string());
+    }
+    Ok(String::from_utf8_lossy(&writer.into_inner().into_inner()).to_string())
 }
 
 use quick_xml::Writer;
@@ -822,12 +1488,382 @@ use std::fs;
 use std::io::Cursor;
 use std::path::PathBuf;
 
+// gzip + url-safe base64, the same format gd uses for the k4 key in the savefile
+pub fn encode_level_string(ls: &str) -> String {
+    use std::io::Write;
+    let mut ls_encoder = gzip::Encoder::new(Vec::new()).unwrap();
+    ls_encoder.write_all(ls.as_bytes()).unwrap();
+    let b64_encrypted = base64::encode(&ls_encoder.finish().into_result().unwrap());
+    let fin = b64_encrypted.replace("+", "-").replace("/", "_");
+    "H4sIAAAAAAAAC".to_string() + &fin[13..]
+}
+
+pub fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out + "\""
+}
+
+// paths are shown relative to the main script, like in the lockfile
+fn relative_path(file: &Path, script_path: &Path) -> String {
+    match script_path.parent() {
+        Some(dir) => file.strip_prefix(dir).unwrap_or(file),
+        None => file,
+    }
+    .to_string_lossy()
+    .to_string()
+}
+
+// a json file saying where in the script every added object came from,
+// in the order the objects are added to the level
+pub fn source_map_json(objects: &[GdObj], script_path: &Path) -> String {
+    let relative = |file: &Path| json_str(&relative_path(file, script_path));
+    let number = |obj: &GdObj, key| match obj.params.get(&key) {
+        Some(ObjParam::Number(n)) => ObjParam::Number(*n).to_string(),
+        _ => "null".to_string(),
+    };
+
+    let mut out = String::from("{\n  \"objects\": [");
+    out += &objects
+        .iter()
+        .enumerate()
+        .map(|(i, obj)| {
+            let source = match &obj.source {
+                Some(s) => format!(
+                    "\"file\": {}, \"line\": {}, \"column\": {}, \"end_line\": {}, \"end_column\": {}, \"macro\": {}, \"defined_in\": {}",
+                    relative(&s.file),
+                    s.pos.0 .0,
+                    s.pos.0 .1 + 1,
+                    s.pos.1 .0,
+                    s.pos.1 .1 + 1,
+                    s.macro_name
+                        .as_deref()
+                        .map(json_str)
+                        .unwrap_or_else(|| "null".to_string()),
+                    relative(&s.def_file)
+                ),
+                // made by the compiler itself, like debug labels
+                None => "\"file\": null".to_string(),
+            };
+            format!(
+                "\n    {{\"index\": {}, \"id\": {}, \"x\": {}, \"y\": {}, {}}}",
+                i,
+                number(obj, 1),
+                number(obj, 2),
+                number(obj, 3),
+                source
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+    out += if objects.is_empty() {
+        "]\n}\n"
+    } else {
+        "\n  ]\n}\n"
+    };
+    out
+}
+
+// how many objects and ids each source line, macro and module of the script is responsible for.
+// `objects` are the objects before their arbitrary ids were replaced, and `id_maps` says
+// what those were replaced with, so ids that ended up shared are only counted once.
+// an id is counted for the first object that uses it
+pub fn cost_report(objects: &[GdObj], id_maps: &IdMaps, script_path: &Path) -> String {
+    const ROWS: usize = 20;
+    // objects, triggers, groups, colors, blocks, items
+    type Cost = [usize; 6];
+
+    let mut seen = HashSet::<(usize, SpecificId)>::new();
+    let mut tables = [
+        HashMap::<String, Cost>::new(), // source line
+        HashMap::<String, Cost>::new(), // macro
+        HashMap::<String, Cost>::new(), // module
+    ];
+
+    for obj in objects {
+        let mut cost: Cost = [1, (obj.mode == ObjectMode::Trigger) as usize, 0, 0, 0, 0];
+
+        let mut props = obj.params.iter().collect::<Vec<(&u16, &ObjParam)>>();
+        props.sort_by_key(|(k, _)| **k);
+        for (_, prop) in props {
+            let (class_index, ids) = match prop {
+                ObjParam::Group(g) => (0, vec![g.id]),
+                ObjParam::GroupList(l) => (0, l.iter().map(|g| g.id).collect()),
+                ObjParam::Color(c) => (1, vec![c.id]),
+                ObjParam::Block(b) => (2, vec![b.id]),
+                ObjParam::Item(i) => (3, vec![i.id]),
+                _ => continue,
+            };
+            for id in ids {
+                if let Id::Arbitrary(id) = id {
+                    if let Some(specific) = id_maps[class_index].get(&id) {
+                        if seen.insert((class_index, *specific)) {
+                            cost[2 + class_index] += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        let keys = match &obj.source {
+            Some(s) => [
+                format!("{}:{}", relative_path(&s.file, script_path), s.pos.0 .0),
+                s.macro_name
+                    .clone()
+                    .unwrap_or_else(|| String::from("(top level)")),
+                relative_path(&s.def_file, script_path),
+            ],
+            None => [
+                String::from("(compiler)"),
+                String::from("(compiler)"),
+                String::from("(compiler)"),
+            ],
+        };
+        for (table, key) in tables.iter_mut().zip(keys.iter()) {
+            let total = table.entry(key.clone()).or_insert([0; 6]);
+            for (t, c) in total.iter_mut().zip(cost.iter()) {
+                *t += c;
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for (table, title) in tables
+        .iter()
+        .zip(["By source line", "By macro", "By module"].iter())
+    {
+        let mut rows = table.iter().collect::<Vec<(&String, &Cost)>>();
+        // most objects first, then most ids
+        rows.sort_by(|(a_key, a), (b_key, b)| {
+            let ids = |c: &Cost| c[2..].iter().sum::<usize>();
+            b[0].cmp(&a[0])
+                .then(ids(b).cmp(&ids(a)))
+                .then(a_key.cmp(b_key))
+        });
+
+        out += &format!(
+            "{}:\n{:>8} {:>8} {:>7} {:>7} {:>7} {:>7}\n",
+            title, "objects", "triggers", "groups", "colors", "blocks", "items"
+        );
+        for (key, c) in rows.iter().take(ROWS) {
+            out += &format!(
+                "{:>8} {:>8} {:>7} {:>7} {:>7} {:>7}  {}\n",
+                c[0], c[1], c[2], c[3], c[4], c[5], key
+            );
+        }
+        if rows.len() > ROWS {
+            out += &format!("     ... and {} more\n", rows.len() - ROWS);
+        }
+        out += "\n";
+    }
+    out.trim_end().to_string()
+}
+
+// turns a (decoded) level string into a json array with one object per gd object,
+// mapping each key to its decoded value
+pub fn level_string_to_json(ls: &str) -> Result<String, String> {
+    fn json_val(key: u16, val: &ObjParam) -> String {
+        match val {
+            // text objects store their text as base64
+            ObjParam::Text(t) if key == 31 => {
+                match base64::decode(&t.replace("-", "+").replace("_", "/")) {
+                    Ok(b) => json_str(&String::from_utf8_lossy(&b)),
+                    Err(_) => json_str(t),
+                }
+            }
+            ObjParam::Text(t) => json_str(t),
+            ObjParam::Bool(b) => b.to_string(),
+            ObjParam::GroupList(_) => format!("[{}]", val.to_string().replace(".", ", ")),
+            _ => val.to_string(),
+        }
+    }
+
+    let (_, objects) = parse_level_string(ls)?;
+    let mut out = String::from("[\n");
+    for (i, obj) in objects.iter().enumerate() {
+        let mut param_list = obj.params.iter().collect::<Vec<(&u16, &ObjParam)>>();
+        param_list.sort_by(|a, b| (*a.0).cmp(b.0));
+
+        out += "  {";
+        out += &param_list
+            .iter()
+            .map(|(key, val)| format!("\"{}\": {}", key, json_val(**key, val)))
+            .collect::<Vec<String>>()
+            .join(", ");
+        out += if i + 1 < objects.len() { "},\n" } else { "}\n" };
+    }
+    Ok(out + "]\n")
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorChannel {
+    pub id: u16,
+    pub rgb: (u8, u8, u8),
+    pub blending: bool,
+    pub opacity: f64,
+}
+
+impl ColorChannel {
+    // the format used in the kS38 key of the level header
+    fn serialize(&self) -> String {
+        format!(
+            "1_{}_2_{}_3_{}_11_255_12_255_13_255_4_-1_{}6_{}_7_{}_15_1_18_0_8_1",
+            self.rgb.0,
+            self.rgb.1,
+            self.rgb.2,
+            if self.blending { "5_1_" } else { "" },
+            self.id,
+            ObjParam::Number(self.opacity)
+        )
+    }
+}
+
+// settings for the level itself, set with $.level_settings, $.level_color
+// or the matching file attributes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelSettings {
+    // these are stored in the level's dictionary in the savefile
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub song: Option<u32>,
+    pub custom_song: Option<u32>,
+
+    // these are stored in the level string header
+    pub game_mode: Option<u8>,
+    pub speed: Option<u8>,
+    pub mini: Option<bool>,
+    pub dual: Option<bool>,
+    pub colors: Vec<ColorChannel>,
+}
+
+impl LevelSettings {
+    pub fn has_header_settings(&self) -> bool {
+        self.game_mode.is_some()
+            || self.speed.is_some()
+            || self.mini.is_some()
+            || self.dual.is_some()
+            || !self.colors.is_empty()
+    }
+
+    // keys in the level's savefile dictionary, with their xml type and new value
+    fn save_values(&self) -> Vec<(&'static str, &'static [u8], String)> {
+        let mut out = Vec::new();
+        if let Some(name) = &self.name {
+            out.push(("k2", &b"s"[..], name.clone()));
+        }
+        if let Some(desc) = &self.description {
+            let encoded = base64::encode(desc.as_bytes())
+                .replace("+", "-")
+                .replace("/", "_");
+            out.push(("k3", &b"s"[..], encoded));
+        }
+        // a custom song overrides the official one, so it has to be reset
+        if let Some(song) = self.song {
+            out.push(("k8", &b"i"[..], song.to_string()));
+            out.push(("k45", &b"i"[..], "0".to_string()));
+        }
+        if let Some(song) = self.custom_song {
+            out.push(("k45", &b"i"[..], song.to_string()));
+        }
+        out
+    }
+
+    // applies the start settings and color channels to a level string header
+    pub fn apply_to_header(&self, header: &str) -> String {
+        let mut pairs = Vec::<(String, String)>::new();
+        let mut split = header.split(',');
+        while let Some(key) = split.next() {
+            if key.is_empty() {
+                continue;
+            }
+            pairs.push((key.to_string(), split.next().unwrap_or("").to_string()));
+        }
+
+        fn set(pairs: &mut Vec<(String, String)>, key: &str, val: String) {
+            match pairs.iter_mut().find(|(k, _)| k == key) {
+                Some(pair) => pair.1 = val,
+                None => pairs.push((key.to_string(), val)),
+            };
+        }
+
+        if let Some(mode) = self.game_mode {
+            set(&mut pairs, "kA2", mode.to_string());
+        }
+        if let Some(mini) = self.mini {
+            set(&mut pairs, "kA3", (mini as u8).to_string());
+        }
+        if let Some(speed) = self.speed {
+            set(&mut pairs, "kA4", speed.to_string());
+        }
+        if let Some(dual) = self.dual {
+            set(&mut pairs, "kA8", (dual as u8).to_string());
+        }
+
+        if !self.colors.is_empty() {
+            let old_colors = match pairs.iter().find(|(k, _)| k == "kS38") {
+                Some((_, v)) => v.clone(),
+                None => String::new(),
+            };
+            let mut colors = old_colors
+                .split('|')
+                .filter(|c| !c.is_empty())
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>();
+
+            for channel in &self.colors {
+                let new_color = channel.serialize();
+                let existing = colors.iter_mut().find(|c| {
+                    let props: Vec<&str> = c.split('_').collect();
+                    (0..props.len() / 2)
+                        .any(|i| props[i * 2] == "6" && props[i * 2 + 1] == channel.id.to_string())
+                });
+                match existing {
+                    Some(c) => *c = new_color,
+                    None => colors.push(new_color),
+                }
+            }
+            set(&mut pairs, "kS38", colors.join("|") + "|");
+        }
+
+        pairs
+            .iter()
+            .map(|(k, v)| format!("{},{}", k, v))
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+// replaces the header of a level string with one that has the level settings applied
+pub fn apply_level_settings(ls: &str, settings: &LevelSettings) -> String {
+    if !settings.has_header_settings() {
+        return ls.to_string();
+    }
+    let (header, objects) = if ls.starts_with('k') {
+        let mut split = ls.splitn(2, ';');
+        (split.next().unwrap_or(""), split.next().unwrap_or(""))
+    } else {
+        ("", ls)
+    };
+    settings.apply_to_header(header) + ";" + objects
+}
+
 pub fn encrypt_level_string(
     ls: String,
     old_ls: String,
     path: PathBuf,
     level_name: Option<String>,
+    settings: &LevelSettings,
 ) -> Result<(), String> {
+    use quick_xml::events::{BytesEnd, BytesStart};
+
     let mut file = fs::File::open(path.clone()).unwrap();
     let mut file_content = Vec::new();
     file.read_to_end(&mut file_content).unwrap();
@@ -843,10 +1879,17 @@ pub fn encrypt_level_string(
 
     let mut buf = Vec::new();
 
-    let mut k4_detected = false;
     let mut done = false;
     let mut k2_detected = false;
-    let mut level_detected = false;
+    let mut in_key = false;
+    // how many dicts deep the reader is, and the depth of the level's dict
+    let mut depth = 0;
+    let mut level_depth = None;
+    // the key of the value that comes next, if it's a key we replace
+    let mut value_key: Option<String> = None;
+    let mut written_keys = Vec::new();
+
+    let save_values = settings.save_values();
 
     //println!("{}", old_ls);
 
@@ -857,49 +1900,87 @@ pub fn encrypt_level_string(
             // unescape and decode the text event using the reader encoding
             Ok(Event::Text(e)) => {
                 let text = e.unescape_and_decode(&reader).unwrap();
-                if k4_detected && level_detected {
-                    let encrypted_ls: String = {
-                        let mut ls_encoder = gzip::Encoder::new(Vec::new()).unwrap();
-                        ls_encoder.write_all(&full_ls.as_bytes()).unwrap();
-                        let b64_encrypted =
-                            base64::encode(&ls_encoder.finish().into_result().unwrap());
-                        let fin = b64_encrypted.replace("+", "-").replace("/", "_");
-                        "H4sIAAAAAAAAC".to_string() + &fin[13..]
-                    };
-
-                    assert!(writer
-                        .write_event(Event::Text(BytesText::from_plain_str(&encrypted_ls)))
-                        .is_ok());
-                    done = true;
-                    k4_detected = false;
-                } else if k4_detected {
-                    k4_detected = false;
-                } else {
-                    assert!(writer.write_event(Event::Text(e)).is_ok())
-                }
 
                 if k2_detected {
-                    if let Some(level_name) = &level_name {
-                        if level_name == &text {
-                            level_detected = true;
-                            println!("Writing to level: {}", text);
-                        }
-                    } else {
-                        level_detected = true;
+                    let detected = match &level_name {
+                        Some(level_name) => level_name == &text,
+                        None => true,
+                    };
+                    if detected {
                         println!("Writing to level: {}", text);
+                        level_depth = Some(depth);
+                        value_key = Some("k2".to_string());
                     }
-
                     k2_detected = false;
                 }
 
-                if !done && text == "k4" {
-                    k4_detected = true
+                let new_text = match value_key.take() {
+                    Some(key) => {
+                        let new_text = if key == "k4" {
+                            Some(encode_level_string(&full_ls))
+                        } else {
+                            save_values
+                                .iter()
+                                .rev()
+                                .find(|(k, _, _)| *k == key)
+                                .map(|(_, _, v)| v.clone())
+                        };
+                        written_keys.push(key);
+                        new_text
+                    }
+                    None => None,
+                };
+
+                match new_text {
+                    Some(t) => assert!(writer
+                        .write_event(Event::Text(BytesText::from_plain_str(&t)))
+                        .is_ok()),
+                    None => assert!(writer.write_event(Event::Text(e)).is_ok()),
                 }
 
-                if !done && text == "k2" {
-                    k2_detected = true
+                if in_key && !done {
+                    if level_depth == Some(depth) {
+                        value_key = Some(text.clone());
+                    } else if level_depth.is_none() && text == "k2" {
+                        k2_detected = true
+                    }
                 }
             }
+            Ok(Event::Start(e)) => {
+                in_key = e.name() == b"k";
+                if e.name() == b"d" {
+                    depth += 1;
+                }
+                assert!(writer.write_event(Event::Start(e)).is_ok())
+            }
+            Ok(Event::End(e)) => {
+                if e.name() == b"d" {
+                    if level_depth == Some(depth) {
+                        // add the keys the level doesn't have yet
+                        for (key, typ, val) in &save_values {
+                            if written_keys.iter().any(|k| k == key) {
+                                continue;
+                            }
+                            written_keys.push(key.to_string());
+                            for event in [
+                                Event::Start(BytesStart::borrowed_name(b"k")),
+                                Event::Text(BytesText::from_plain_str(key)),
+                                Event::End(BytesEnd::borrowed(b"k")),
+                                Event::Start(BytesStart::borrowed_name(typ)),
+                                Event::Text(BytesText::from_plain_str(val)),
+                                Event::End(BytesEnd::borrowed(typ)),
+                            ] {
+                                assert!(writer.write_event(event).is_ok());
+                            }
+                        }
+                        level_depth = None;
+                        done = true;
+                    }
+                    depth -= 1;
+                }
+                in_key = false;
+                assert!(writer.write_event(Event::End(e)).is_ok())
+            }
             Ok(Event::Eof) => break, // exits the loop when reaching end of file
             Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
             Ok(e) => assert!(writer.write_event(e).is_ok()),
@@ -927,7 +2008,7 @@ pub fn encrypt_level_string(
         type AesEcb = Ecb<Aes256, Pkcs7>;
 
         // re-create cipher mode instance
-        let cipher = AesEcb::new_var(&IOS_KEY, &[]).unwrap();
+        let cipher = AesEcb::new_var(IOS_KEY, &[]).unwrap();
 
         let fin = cipher.encrypt_vec(&bytes);
         assert!(fs::write(path, fin).is_ok());
@@ -959,3 +2040,757 @@ pub fn encrypt_level_string(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn parse_objects() {
+        let obj = parse_obj("1,1006,51,3,52,1,57,2.4.6,62,1,87,0,10,0.5,").unwrap();
+        assert_eq!(obj.mode, ObjectMode::Trigger);
+        // a pulse trigger targeting a group
+        assert_eq!(obj.params[&51], specific(group, 3));
+        assert_eq!(
+            obj.params[&57],
+            ObjParam::GroupList(
+                [2, 4, 6]
+                    .iter()
+                    .map(|g| Group {
+                        id: Id::Specific(*g)
+                    })
+                    .collect()
+            )
+        );
+        assert_eq!(obj.params[&62], ObjParam::Bool(true));
+        assert_eq!(obj.params[&87], ObjParam::Bool(false));
+        assert_eq!(obj.params[&10], ObjParam::Number(0.5));
+
+        // a pulse trigger targeting a color channel, and a collision block
+        let obj = parse_obj("1,1006,51,3").unwrap();
+        assert_eq!(
+            obj.params[&51],
+            ObjParam::Color(Color {
+                id: Id::Specific(3)
+            })
+        );
+        let obj = parse_obj("1,1816,80,7,57,5").unwrap();
+        assert_eq!(obj.mode, ObjectMode::Object);
+        assert_eq!(
+            obj.params[&80],
+            ObjParam::Block(Block {
+                id: Id::Specific(7)
+            })
+        );
+        assert_eq!(obj.params[&57], specific(group, 5));
+
+        assert!(parse_obj("1,1,2").is_err());
+        assert!(parse_obj("1,1,a,2").is_err());
+        assert!(parse_obj("1,1,57,a").is_err());
+    }
+
+    #[test]
+    fn unreadable_objects_keep_their_ids() {
+        let used = get_used_ids("kS38,1_40|;1,1,57,3;1,1816,80,7;").unwrap();
+        assert!(used[0].contains(&3));
+        assert!(used[2].contains(&7));
+        // the groups of an object that can't be read would be handed out again
+        assert!(get_used_ids("1,1,57,3;1,1,57,a;").is_err());
+        assert!(append_objects(
+            Vec::new(),
+            "1,1,57",
+            None,
+            &Default::default(),
+            &Default::default(),
+            &Default::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn level_string_round_trip() {
+        let objects = vec![
+            obj(
+                ObjectMode::Trigger,
+                vec![
+                    (1, ObjParam::Number(901.0)),
+                    (51, specific(group, 5)),
+                    (10, ObjParam::Number(0.5)),
+                    (28, ObjParam::Number(-30.0)),
+                    (62, ObjParam::Bool(true)),
+                    (87, ObjParam::Bool(false)),
+                    (
+                        57,
+                        ObjParam::GroupList(vec![
+                            Group {
+                                id: Id::Specific(2),
+                            },
+                            Group {
+                                id: Id::Specific(3),
+                            },
+                        ]),
+                    ),
+                ],
+            ),
+            obj(
+                ObjectMode::Trigger,
+                vec![
+                    (1, ObjParam::Number(1811.0)),
+                    (51, specific(group, 6)),
+                    (56, ObjParam::Bool(true)),
+                    (77, ObjParam::Number(3.0)),
+                    (
+                        80,
+                        ObjParam::Item(Item {
+                            id: Id::Specific(4),
+                        }),
+                    ),
+                ],
+            ),
+            obj(
+                ObjectMode::Object,
+                vec![
+                    (1, ObjParam::Number(1816.0)),
+                    (
+                        80,
+                        ObjParam::Block(Block {
+                            id: Id::Specific(7),
+                        }),
+                    ),
+                    (
+                        21,
+                        ObjParam::Color(Color {
+                            id: Id::Specific(8),
+                        }),
+                    ),
+                    (31, ObjParam::Text("aGk=".to_string())),
+                    (57, specific(group, 9)),
+                ],
+            ),
+        ];
+        let (new_ls, _, _) = append_objects(
+            objects.clone(),
+            "",
+            None,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+        )
+        .unwrap();
+
+        let header = "kS38,1_40_2_125_3_255|,kA13,0";
+        let (parsed_header, parsed) =
+            parse_level_string(&format!("{};{}", header, new_ls)).unwrap();
+        assert_eq!(parsed_header, header);
+        assert_eq!(parsed.len(), objects.len());
+
+        for (before, mut after) in objects.into_iter().zip(parsed) {
+            assert_eq!(after.mode, before.mode);
+            // the group spwn adds to everything it makes
+            let groups = match after.params.remove(&57) {
+                Some(ObjParam::GroupList(l)) => l,
+                Some(ObjParam::Group(g)) => vec![g],
+                a => panic!("expected groups, found {:?}", a),
+            };
+            assert_eq!(groups.last(), Some(&SPWN_SIGNATURE_GROUP));
+            let groups = &groups[..groups.len() - 1];
+            match before.params.get(&57) {
+                Some(ObjParam::GroupList(l)) => assert_eq!(groups, &l[..]),
+                Some(ObjParam::Group(g)) => assert_eq!(groups, &[*g]),
+                _ => assert!(groups.is_empty()),
+            }
+            if after.mode == ObjectMode::Trigger {
+                assert_eq!(after.params.remove(&108), Some(ObjParam::Number(1.0)));
+            }
+            let mut before = before.params;
+            before.remove(&57);
+            assert_eq!(after.params, before);
+        }
+    }
+
+    #[test]
+    fn namespaces() {
+        let block = |g| {
+            vec![obj(
+                ObjectMode::Object,
+                vec![(1, ObjParam::Number(1.0)), (57, specific(group, g))],
+            )]
+        };
+        // the lowest group the level and the script don't use
+        let (ls_a, _, _) = append_objects(
+            block(2),
+            "",
+            Some("a"),
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+        )
+        .unwrap();
+        let (ls_b, _, _) = append_objects(
+            block(4),
+            &ls_a,
+            Some("b"),
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+        )
+        .unwrap();
+        let mut ls = format!("{}{}1,1,57,1001;", ls_a, ls_b);
+        let expected = [("a".to_string(), 1), ("b".to_string(), 3)];
+        assert_eq!(
+            level_namespaces(&ls),
+            Ok(expected.iter().cloned().collect())
+        );
+
+        // a namespace's objects, its marker and the objects from builds without a namespace
+        let mut all = ls.clone();
+        assert_eq!(remove_spwn_objects(&mut ls, Some("a")), Ok(3));
+        assert_eq!(
+            level_namespaces(&ls),
+            Ok(expected[1..].iter().cloned().collect())
+        );
+        assert_eq!(remove_spwn_objects(&mut all, None), Ok(5));
+        assert_eq!(all, "");
+
+        // console output has no namespace
+        let (ls, _, _) = append_objects(
+            block(2),
+            "",
+            None,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(ls, "1,1,57,2.1001,;");
+
+        let namespace = |code: &str| {
+            let path = std::path::PathBuf::from("test.spwn");
+            let (_, notes) = crate::parser::parse_spwn(code.to_string(), path.clone()).unwrap();
+            crate::compiler::script_namespace(&notes, &path)
+        };
+        assert_eq!(namespace("#[namespace(\"x\")]"), Ok("x".to_string()));
+        assert_eq!(namespace(""), Ok("test".to_string()));
+        assert!(namespace("#[namespace(\"\")]").is_err());
+    }
+
+    // a block in each of the groups, so they are all used
+    fn blocks_in_groups(count: u16) -> Vec<GdObj> {
+        (1..=count)
+            .map(|i| {
+                obj(
+                    ObjectMode::Object,
+                    vec![(1, ObjParam::Number(1.0)), (57, group(Id::Arbitrary(i)))],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reserved_ranges() {
+        let mut reserved = ReservedIds::default();
+        reserved.add("1..10g, 12g,5c").unwrap();
+        assert_eq!(reserved.find(0, 1), Some("1..10g".to_string()));
+        assert_eq!(reserved.find(0, 9), Some("1..10g".to_string()));
+        assert_eq!(reserved.find(0, 10), None);
+        assert_eq!(reserved.find(0, 12), Some("12g".to_string()));
+        assert_eq!(reserved.find(1, 5), Some("5c".to_string()));
+        assert_eq!(reserved.find(3, 5), None);
+
+        for invalid in &["10..1g", "5", "5x", "a..bg", "3..3g"] {
+            assert!(ReservedIds::default().add(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn arbitrary_ids_skip_reserved_ranges() {
+        let mut reserved = ReservedIds::default();
+        reserved.add("1..10g,11g").unwrap();
+        let (_, used, id_maps) = append_objects(
+            blocks_in_groups(3),
+            "",
+            None,
+            &Default::default(),
+            &reserved,
+            &Default::default(),
+        )
+        .unwrap();
+        let mut ids = id_maps[0].values().copied().collect::<Vec<u16>>();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![10, 12, 13]);
+        assert_eq!(used[0], 3);
+
+        // ids from the lockfile that are reserved now aren't used
+        let mut preferred = IdMaps::default();
+        preferred[0].insert(1, 5);
+        preferred[0].insert(2, 20);
+        let (_, _, id_maps) = append_objects(
+            blocks_in_groups(2),
+            "",
+            None,
+            &preferred,
+            &reserved,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(id_maps[0][&1], 10);
+        assert_eq!(id_maps[0][&2], 20);
+
+        // neither is the namespace group
+        let (ls, used, _) = append_objects(
+            Vec::new(),
+            "",
+            Some("test"),
+            &Default::default(),
+            &reserved,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(used[0], 1);
+        assert_eq!(
+            level_namespaces(&ls),
+            Ok(vec![("test".to_string(), 10)].into_iter().collect())
+        );
+    }
+
+    #[test]
+    fn reserved_ids_used_by_the_script() {
+        let mut reserved = ReservedIds::default();
+        reserved.add("1..10g").unwrap();
+        let objects = vec![obj(
+            ObjectMode::Object,
+            vec![(1, ObjParam::Number(1.0)), (57, specific(group, 5))],
+        )];
+        let err = append_objects(
+            objects,
+            "",
+            None,
+            &Default::default(),
+            &reserved,
+            &Default::default(),
+        )
+        .unwrap_err();
+        assert!(err.contains("1..10g"), "{}", err);
+    }
+
+    #[test]
+    fn target_id_limits() {
+        let build_reserved = |count, target: &str, reserved: &ReservedIds| {
+            append_objects(
+                blocks_in_groups(count),
+                "",
+                Some("test"),
+                &Default::default(),
+                reserved,
+                &Target::from_name(target).unwrap(),
+            )
+        };
+        let build = |count, target: &str| build_reserved(count, target, &Default::default());
+        // one of them is the namespace group
+        assert!(build(998, "gd2.1").is_ok());
+        assert!(build(999, "gd2.1").is_err());
+
+        // 2.2 has more groups, but not the one SPWN uses to mark its objects
+        let (_, _, id_maps) = build(1100, "gd2.2").unwrap();
+        let ids = id_maps[0].values().copied().collect::<HashSet<u16>>();
+        assert_eq!(ids.len(), 1100);
+        assert!(!ids.iter().any(|id| is_spwn_id(0, *id)));
+        assert_eq!(ids.iter().max(), Some(&1102));
+
+        // with everything up to 9990 reserved, only 9990..=9999 is left
+        let mut reserved = ReservedIds::default();
+        reserved.add("1..9990g").unwrap();
+        assert!(build_reserved(9, "gd2.2", &reserved).is_ok());
+        assert!(build_reserved(10, "gd2.2", &reserved).is_err());
+    }
+
+    #[test]
+    fn level_headers() {
+        let settings = LevelSettings {
+            game_mode: Some(1),
+            mini: Some(true),
+            colors: vec![ColorChannel {
+                id: 1000,
+                rgb: (40, 125, 255),
+                blending: true,
+                opacity: 0.5,
+            }],
+            ..Default::default()
+        };
+        // the existing channel 1000 is replaced, 1001 and the other keys are kept
+        assert_eq!(
+            settings.apply_to_header("kS38,1_0_2_0_3_0_6_1000|1_0_2_0_3_0_6_1001|,kA2,0,kA4,1"),
+            "kS38,1_40_2_125_3_255_11_255_12_255_13_255_4_-1_5_1_6_1000_7_0.500_15_1_18_0_8_1|1_0_2_0_3_0_6_1001|,kA2,1,kA4,1,kA3,1"
+        );
+
+        // levels without a header get one, and nothing changes without header settings
+        let speed = LevelSettings {
+            speed: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(apply_level_settings("1,1;", &speed), "kA4,2;1,1;");
+        assert_eq!(apply_level_settings("kA4,0;1,1;", &speed), "kA4,2;1,1;");
+        assert_eq!(
+            apply_level_settings("kA4,0;1,1;", &LevelSettings::default()),
+            "kA4,0;1,1;"
+        );
+    }
+
+    #[test]
+    fn level_save_values() {
+        let settings = LevelSettings {
+            name: Some("test".to_string()),
+            description: Some("hi?>".to_string()),
+            song: Some(5),
+            ..Default::default()
+        };
+        let values = settings
+            .save_values()
+            .into_iter()
+            .map(|(k, _, v)| (k, v))
+            .collect::<Vec<(&str, String)>>();
+        // the description is url safe base64, and the official song resets the custom one
+        assert_eq!(
+            values,
+            vec![
+                ("k2", "test".to_string()),
+                ("k3", "aGk_Pg==".to_string()),
+                ("k8", "5".to_string()),
+                ("k45", "0".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn gmd_level_strings() {
+        let gmd = format!(
+            "<?xml version=\"1.0\"?><plist version=\"1.0\" gjver=\"2.0\"><d><k>kCEK</k><i>4</i><k>k2</k><s>test</s><k>k4</k><s>{}</s></d></plist>",
+            encode_level_string("kA4,0;1,1,57,1001;")
+        );
+        assert_eq!(get_gmd_level_string(&gmd).unwrap(), "kA4,0;1,1,57,1001;");
+
+        let mut ls = get_gmd_level_string(&gmd).unwrap();
+        assert_eq!(remove_spwn_objects(&mut ls, None), Ok(1));
+        let new_gmd = set_gmd_level_string(&gmd, &ls).unwrap();
+        assert_eq!(get_gmd_level_string(&new_gmd).unwrap(), "kA4,0;");
+        assert!(new_gmd.contains("<k>k2</k><s>test</s>"));
+
+        assert!(set_gmd_level_string("<plist><d></d></plist>", "").is_err());
+    }
+
+    // a function with `spawned` spawn triggered triggers in it, made in `file`
+    fn func_id(parent: Option<usize>, file: &str, spawned: u16) -> FunctionId {
+        let mut func_id = function(
+            (0..spawned)
+                .map(|i| {
+                    let (trigger, _) = trigger(vec![
+                        (1, ObjParam::Number(901.0)),
+                        (57, group(Id::Arbitrary(i + 1))),
+                    ]);
+                    (trigger, i as usize)
+                })
+                .collect(),
+        );
+        func_id.parent = parent;
+        func_id.file = Some(PathBuf::from(file));
+        func_id
+    }
+
+    // a trigger that runs when the player passes it
+    fn start_trigger() -> (GdObj, usize) {
+        trigger(vec![(1, ObjParam::Number(901.0))])
+    }
+
+    // the spots in a column of triggers with the default origin
+    fn column(x: f64, rows: std::ops::Range<u16>) -> Vec<(f64, f64)> {
+        let top = ((80 - START_HEIGHT) * 30 + 15) as f64;
+        rows.map(|r| (x, top - r as f64 * 30.0)).collect()
+    }
+
+    fn positions(objects: &[GdObj]) -> Vec<(f64, f64)> {
+        objects.iter().filter_map(obj_pos).collect()
+    }
+
+    #[test]
+    fn layouts() {
+        let mut func_ids = vec![
+            func_id(None, "a.spwn", 3),
+            func_id(Some(0), "a.spwn", 2),
+            func_id(None, "b.spwn", 1),
+        ];
+        func_ids[0].obj_list.push(start_trigger());
+        let layout = |strategy| Layout {
+            strategy,
+            ..Default::default()
+        };
+
+        // a column for every function. the trigger at x = 0 is in the
+        // same block as the top of the first column, so that spot is skipped
+        let objects = apply_fn_ids(&func_ids, &layout(LayoutStrategy::Columns), &[]);
+        let mut expected = column(15.0, 1..4);
+        expected.extend(column(0.0, 0..1));
+        expected.extend(column(45.0, 0..2));
+        expected.extend(column(75.0, 0..1));
+        assert_eq!(positions(&objects), expected);
+        assert!(objects[..3]
+            .iter()
+            .all(|o| o.params.get(&62) == Some(&ObjParam::Bool(true))));
+        assert_eq!(objects[3].params.get(&62), None);
+
+        // six spawned triggers, in columns of three
+        let objects = apply_fn_ids(&func_ids, &layout(LayoutStrategy::Grid), &[]);
+        let mut expected = column(15.0, 1..3);
+        expected.extend(column(45.0, 0..1));
+        expected.extend(column(0.0, 0..1));
+        expected.extend(column(45.0, 1..3));
+        expected.extend(column(75.0, 0..1));
+        assert_eq!(positions(&objects), expected);
+
+        // the second file starts three columns after the first one
+        let objects = apply_fn_ids(&func_ids, &layout(LayoutStrategy::Modules), &[]);
+        let mut expected = column(15.0, 1..4);
+        expected.extend(column(0.0, 0..1));
+        expected.extend(column(45.0, 0..2));
+        expected.extend(column(165.0, 0..1));
+        assert_eq!(positions(&objects), expected);
+    }
+
+    #[test]
+    fn layout_origins() {
+        let mut func_ids = vec![func_id(None, "a.spwn", 2)];
+        let level = vec![obj(
+            ObjectMode::Object,
+            vec![
+                (1, ObjParam::Number(1.0)),
+                (2, ObjParam::Number(400.0)),
+                (3, ObjParam::Number(-15.0)),
+            ],
+        )];
+
+        let layout = Layout {
+            origin: Layout::parse_origin("-3000,-15").unwrap(),
+            editor_layer: Some(4),
+            ..Default::default()
+        };
+        let objects = apply_fn_ids(&func_ids, &layout, &level);
+        assert_eq!(
+            positions(&objects),
+            vec![(-3000.0, -15.0), (-3000.0, -45.0)]
+        );
+        assert!(objects
+            .iter()
+            .all(|o| o.params.get(&20) == Some(&ObjParam::Number(4.0))));
+
+        // spots with something in them are skipped
+        let layout = Layout {
+            origin: LayoutOrigin::At(400.0, 15.0),
+            ..Default::default()
+        };
+        let objects = apply_fn_ids(&func_ids, &layout, &level);
+        assert_eq!(positions(&objects), vec![(400.0, 15.0), (400.0, -45.0)]);
+        assert_eq!(objects[0].params.get(&20), None);
+
+        let layout = Layout {
+            origin: Layout::parse_origin("after-level").unwrap(),
+            ..Default::default()
+        };
+        let objects = apply_fn_ids(&func_ids, &layout, &level);
+        assert_eq!(objects[0].params.get(&2), Some(&ObjParam::Number(465.0)));
+
+        // triggers at x = 0 skip them too
+        func_ids[0].obj_list.push(start_trigger());
+        let (x, y) = column(0.0, 0..1)[0];
+        let level = vec![obj(
+            ObjectMode::Object,
+            vec![(2, ObjParam::Number(x)), (3, ObjParam::Number(y))],
+        )];
+        let objects = apply_fn_ids(&func_ids, &Default::default(), &level);
+        let mut expected = column(15.0, 2..4);
+        expected.extend(column(0.0, 1..2));
+        assert_eq!(positions(&objects), expected);
+
+        assert!(Layout::parse_origin("1,2,3").is_err());
+        assert!(Layout::parse_strategy("spiral").is_err());
+    }
+
+    #[test]
+    fn debug_labels() {
+        let globals = compile(
+            "f = !{\n    $.add(trigger {1: 901, 51: 10g})\n}\nhelper = () {\n    !{\n        $.add(trigger {1: 901, 51: 11g})\n    }!\n}\nhelper()\n",
+            Vec::new(),
+        )
+        .unwrap();
+        let labels = |level: &[GdObj]| {
+            let layout = Layout {
+                debug_labels: true,
+                ..Default::default()
+            };
+            apply_fn_ids(&globals.func_ids, &layout, level)
+                .iter()
+                .filter(|o| o.params.get(&1) == Some(&ObjParam::Number(914.0)))
+                .map(|o| {
+                    let text = match &o.params[&31] {
+                        ObjParam::Text(t) => String::from_utf8(base64::decode(t).unwrap()).unwrap(),
+                        _ => unreachable!(),
+                    };
+                    (obj_pos(o).unwrap(), text)
+                })
+                .collect::<Vec<((f64, f64), String)>>()
+        };
+
+        // the lines are one off, since compile puts #[no_std] above the script.
+        // the main function has no spawned triggers, so it doesn't get a label
+        let above = column(0.0, 0..1)[0].1 + 30.0;
+        assert_eq!(
+            labels(&[]),
+            vec![
+                ((15.0, above), "f test.spwn:2:1".to_string()),
+                ((45.0, above), "helper test.spwn:6:5".to_string()),
+            ]
+        );
+
+        // labels go higher up if something is in the way
+        let level = vec![obj(
+            ObjectMode::Object,
+            vec![(2, ObjParam::Number(15.0)), (3, ObjParam::Number(above))],
+        )];
+        assert_eq!(labels(&level)[0].0, (15.0, above + 30.0));
+    }
+
+    #[test]
+    fn object_sources() {
+        let globals = compile(
+            "add_block = () {\n    $.add(obj {1: 1})\n}\n$.add(obj {1: 2})\nadd_block()\n",
+            Vec::new(),
+        )
+        .unwrap();
+        let sources = globals
+            .objects
+            .iter()
+            .map(|o| {
+                let s = o.source.as_ref().unwrap();
+                (s.pos.0 .0, s.macro_name.clone(), s.file == s.def_file)
+            })
+            .collect::<Vec<(usize, Option<String>, bool)>>();
+        // the object from the macro points at the call. the lines are one off,
+        // since compile puts #[no_std] above the script
+        assert_eq!(
+            sources,
+            vec![(5, None, true), (6, Some("add_block".to_string()), true)]
+        );
+    }
+
+    #[test]
+    fn source_maps() {
+        let source = |file: &str, macro_name: Option<&str>| ObjSource {
+            file: PathBuf::from(file),
+            pos: ((3, 4), (3, 10)),
+            macro_name: macro_name.map(String::from),
+            def_file: PathBuf::from("/project/lib/a.spwn"),
+        };
+        let mut objects = vec![
+            obj(
+                ObjectMode::Object,
+                vec![
+                    (1, ObjParam::Number(1.0)),
+                    (2, ObjParam::Number(15.0)),
+                    (3, ObjParam::Number(45.0)),
+                ],
+            ),
+            obj(ObjectMode::Trigger, vec![(1, ObjParam::Number(901.0))]),
+            obj(ObjectMode::Object, vec![(1, ObjParam::Number(914.0))]),
+        ];
+        objects[0].source = Some(source("/project/main.spwn", Some("add")));
+        // the same place twice still gets one entry per object
+        objects[1].source = Some(source("/elsewhere/b.spwn", None));
+
+        assert_eq!(
+            source_map_json(&objects, Path::new("/project/main.spwn")),
+            concat!(
+                "{\n  \"objects\": [\n",
+                "    {\"index\": 0, \"id\": 1, \"x\": 15, \"y\": 45, \"file\": \"main.spwn\", \"line\": 3, \"column\": 5, \"end_line\": 3, \"end_column\": 11, \"macro\": \"add\", \"defined_in\": \"lib/a.spwn\"},\n",
+                "    {\"index\": 1, \"id\": 901, \"x\": null, \"y\": null, \"file\": \"/elsewhere/b.spwn\", \"line\": 3, \"column\": 5, \"end_line\": 3, \"end_column\": 11, \"macro\": null, \"defined_in\": \"lib/a.spwn\"},\n",
+                "    {\"index\": 2, \"id\": 914, \"x\": null, \"y\": null, \"file\": null}\n",
+                "  ]\n}\n",
+            )
+        );
+        assert_eq!(
+            source_map_json(&[], Path::new("main.spwn")),
+            "{\n  \"objects\": []\n}\n"
+        );
+    }
+
+    #[test]
+    fn cost_reports() {
+        let arbitrary = |class: fn(Id) -> ObjParam, id| class(Id::Arbitrary(id));
+        let mut objects = vec![
+            obj(
+                ObjectMode::Trigger,
+                vec![
+                    (51, arbitrary(group, 1)),
+                    (
+                        57,
+                        ObjParam::GroupList(vec![
+                            Group {
+                                id: Id::Arbitrary(1),
+                            },
+                            Group {
+                                id: Id::Arbitrary(2),
+                            },
+                        ]),
+                    ),
+                ],
+            ),
+            // group 3 became the same id as group 1, and group 9 was already specific
+            obj(
+                ObjectMode::Trigger,
+                vec![
+                    (51, arbitrary(group, 3)),
+                    (80, arbitrary(item, 1)),
+                    (71, specific(group, 9)),
+                ],
+            ),
+            obj(
+                ObjectMode::Object,
+                vec![(
+                    21,
+                    ObjParam::Color(Color {
+                        id: Id::Arbitrary(1),
+                    }),
+                )],
+            ),
+        ];
+        for o in &mut objects[..2] {
+            o.source = Some(ObjSource {
+                file: PathBuf::from("/project/main.spwn"),
+                pos: ((3, 4), (3, 10)),
+                macro_name: Some(String::from("spawner")),
+                def_file: PathBuf::from("/project/lib/a.spwn"),
+            });
+        }
+        // each id is only counted once, for the first object using it
+        let mut id_maps = IdMaps::default();
+        id_maps[0].extend(vec![(1, 10), (2, 11), (3, 10)]);
+        id_maps[1].insert(1, 5);
+        id_maps[3].insert(1, 7);
+
+        assert_eq!(
+            cost_report(&objects, &id_maps, Path::new("/project/main.spwn")),
+            concat!(
+                "By source line:\n",
+                " objects triggers  groups  colors  blocks   items\n",
+                "       2        2       2       0       0       1  main.spwn:3\n",
+                "       1        0       0       1       0       0  (compiler)\n\n",
+                "By macro:\n",
+                " objects triggers  groups  colors  blocks   items\n",
+                "       2        2       2       0       0       1  spawner\n",
+                "       1        0       0       1       0       0  (compiler)\n\n",
+                "By module:\n",
+                " objects triggers  groups  colors  blocks   items\n",
+                "       2        2       2       0       0       1  lib/a.spwn\n",
+                "       1        0       0       1       0       0  (compiler)",
+            )
+        );
+    }
+}
@@ -1,38 +1,47 @@
 //#![feature(arbitrary_enum_discriminant)]
-
-mod ast;
-mod builtin;
-mod compiler;
-mod compiler_types;
-mod documentation;
-mod fmt;
-mod levelstring;
-mod parser;
-
-mod optimize;
+// errors carry the compiler info of where they happened
+#![allow(clippy::result_large_err)]
+
+pub mod ast;
+pub mod builtin;
+pub mod compiler;
+pub mod compiler_info;
+pub mod compiler_types;
+pub mod context;
+pub mod documentation;
+pub mod fmt;
+pub mod globals;
+pub mod icalgebra;
+pub mod inspect;
+pub mod levelstring;
+pub mod lockfile;
+pub mod parser;
+pub mod simulate;
+pub mod value;
+pub mod value_storage;
+
+pub mod optimize;
+pub mod target;
+#[cfg(test)]
+mod test_util;
 
 //#[cfg_attr(target_os = "macos", path = "editorlive_mac.rs")]
 //#[cfg_attr(windows, path = "editorlive_win.rs")]
 //mod editorlive;
 
-use termcolor::Color;
 use optimize::optimize;
+use termcolor::Color;
 
 use parser::*;
 
-
 use std::path::PathBuf;
 
-
 use std::io;
 use tempfile::NamedTempFile;
 
-
 //library has no console output
-pub fn print_with_color(_text: &str, _color: Color) {
-}
-pub fn eprint_with_color(_text: &str, _color: Color) {
-}
+pub fn print_with_color(_text: &str, _color: Color) {}
+pub fn eprint_with_color(_text: &str, _color: Color) {}
 
 pub const STD_PATH: &str = "std";
 
@@ -40,7 +49,13 @@ pub const STD_PATH: &str = "std";
 pub struct Compiler {
     opti_enabled: bool,
     included_paths: Vec<PathBuf>,
-    unparsed_code: String
+    unparsed_code: String,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Compiler {
@@ -49,9 +64,13 @@ impl Compiler {
             opti_enabled: true,
             included_paths: vec![
                 std::env::current_dir().expect("Cannot access current directory"),
-                std::env::current_exe().expect("Cannot access directory of executable").parent().expect("Executable must be in some directory").to_path_buf(),
+                std::env::current_exe()
+                    .expect("Cannot access directory of executable")
+                    .parent()
+                    .expect("Executable must be in some directory")
+                    .to_path_buf(),
             ],
-            unparsed_code: String::new()
+            unparsed_code: String::new(),
         }
     }
     pub fn add_include(&mut self, path_str: String) -> io::Result<()> {
@@ -65,29 +84,60 @@ impl Compiler {
     pub fn set_code(&mut self, code: String) {
         self.unparsed_code = code;
     }
-    pub fn _run(code: String, included_paths: Vec<PathBuf>, opti_enabled: bool) -> Result<String, Box<dyn std::error::Error>> {
+    pub fn set_optimize(&mut self, enabled: bool) {
+        self.opti_enabled = enabled;
+    }
+    pub fn run(&self) -> Result<String, Box<dyn std::error::Error>> {
+        Self::_run(
+            self.unparsed_code.clone(),
+            self.included_paths.clone(),
+            self.opti_enabled,
+        )
+    }
+    pub fn _run(
+        code: String,
+        included_paths: Vec<PathBuf>,
+        opti_enabled: bool,
+    ) -> Result<String, Box<dyn std::error::Error>> {
         let file = NamedTempFile::new()?;
         let mut pbuf = PathBuf::new();
         pbuf.push(file.path());
         let (statements, notes) = parse_spwn(code, pbuf.clone())?;
 
-        let mut compiled = compiler::compile_spwn(
-            statements,
-            pbuf,
-            included_paths,
-            notes,
-        )?;
+        let mut compiled =
+            compiler::compile_spwn(statements, pbuf, included_paths, notes, Vec::new())?;
 
+        // triggers added with $.register_trigger
+        let mut target = target::Target::default();
+        target.triggers.extend(compiled.trigger_table.clone());
+
+        let passes = if opti_enabled {
+            optimize::OptPasses::default()
+        } else {
+            optimize::OptPasses::from_level(0)?
+        };
         let has_stuff = compiled.func_ids.iter().any(|x| !x.obj_list.is_empty());
-        if opti_enabled && has_stuff {
-            compiled.func_ids = optimize(compiled.func_ids, compiled.closed_groups);
+        if has_stuff {
+            compiled.func_ids =
+                optimize(compiled.func_ids, compiled.closed_groups, &target, passes).0;
         }
 
-        let mut objects = levelstring::apply_fn_ids(&compiled.func_ids);
+        let mut objects = levelstring::apply_fn_ids(&compiled.func_ids, &Default::default(), &[]);
 
         objects.extend(compiled.objects);
 
-        let (new_ls, _) = levelstring::append_objects(objects, &String::new())?;
+        if passes.recycle_groups {
+            optimize::recycle_groups(&mut objects, &target);
+        }
+
+        let (new_ls, _, _) = levelstring::append_objects(
+            objects,
+            "",
+            None,
+            &Default::default(),
+            &Default::default(),
+            &target,
+        )?;
 
         Ok(new_ls)
     }
@@ -0,0 +1,313 @@
+// keeps arbitrary IDs the same between builds by writing down which specific IDs they got
+use crate::builtin::{ArbitraryId, SpecificId};
+use crate::levelstring::json_str;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+const CLASS_NAMES: [&str; 4] = ["g", "c", "b", "i"];
+
+// where an arbitrary ID was made
+#[derive(Clone, Debug, PartialEq)]
+pub struct IdOrigin {
+    // file:line:column of the ?g, ?c, ?b or ?i (or the trigger function)
+    pub site: String,
+    // the variable it was first stored in, if any
+    pub name: Option<String>,
+}
+
+impl IdOrigin {
+    // names are used when there is one, since they don't move when the script is edited
+    fn key(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => self.site.clone(),
+        }
+    }
+}
+
+pub type IdOrigins = [HashMap<ArbitraryId, IdOrigin>; 4];
+pub type IdMaps = [HashMap<ArbitraryId, SpecificId>; 4];
+
+// the same site or name can make more than one ID (in a loop or a macro),
+// so every key gets an index for how many times it has been used before
+fn origin_keys(origins: &HashMap<ArbitraryId, IdOrigin>) -> Vec<(ArbitraryId, String)> {
+    let mut ids = origins.keys().copied().collect::<Vec<ArbitraryId>>();
+    // arbitrary IDs are counted up while compiling, so this is the order they were made in
+    ids.sort_unstable();
+
+    let mut uses = HashMap::<String, usize>::new();
+    ids.into_iter()
+        .map(|id| {
+            let key = origins[&id].key();
+            let count = uses.entry(key.clone()).or_insert(0);
+            *count += 1;
+            (id, format!("{}#{}", key, *count - 1))
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Lockfile {
+    // for each ID class: origin key -> specific ID
+    pub ids: [HashMap<String, SpecificId>; 4],
+}
+
+impl Lockfile {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let mut lock = Lockfile::default();
+        if !path.exists() {
+            return Ok(lock);
+        }
+        let content = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("Problem reading {}: {}", path.to_string_lossy(), e)),
+        };
+
+        // each line is "[class] [id] [key]"
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(3, ' ');
+            let class = parts
+                .next()
+                .and_then(|c| CLASS_NAMES.iter().position(|n| *n == c));
+            let id = parts.next().and_then(|id| id.parse::<SpecificId>().ok());
+            match (class, id, parts.next()) {
+                (Some(class), Some(id), Some(key)) => {
+                    lock.ids[class].insert(key.to_string(), id);
+                }
+                _ => {
+                    return Err(format!(
+                        "Invalid line in {} (line {}): {}",
+                        path.to_string_lossy(),
+                        i + 1,
+                        line
+                    ))
+                }
+            }
+        }
+        Ok(lock)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let mut out = String::from(
+            "# written by spwn: which IDs the arbitrary IDs of the script got\n# [class] [id] [origin]\n",
+        );
+        for (class, ids) in self.ids.iter().enumerate() {
+            let mut ids = ids.iter().collect::<Vec<(&String, &SpecificId)>>();
+            ids.sort_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(b.0)));
+            for (key, id) in ids {
+                out += &format!("{} {} {}\n", CLASS_NAMES[class], id, key);
+            }
+        }
+        match fs::write(path, out) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Problem writing {}: {}", path.to_string_lossy(), e)),
+        }
+    }
+
+    // the IDs the arbitrary IDs of this build got last time
+    pub fn preferred_ids(&self, origins: &IdOrigins) -> IdMaps {
+        let mut out = IdMaps::default();
+        for class in 0..4 {
+            for (id, key) in origin_keys(&origins[class]) {
+                if let Some(specific) = self.ids[class].get(&key) {
+                    out[class].insert(id, *specific);
+                }
+            }
+        }
+        out
+    }
+
+    // makes a lockfile from the IDs given out in this build
+    pub fn from_build(origins: &IdOrigins, id_maps: &IdMaps) -> Self {
+        let mut lock = Lockfile::default();
+        for class in 0..4 {
+            for (id, key) in origin_keys(&origins[class]) {
+                if let Some(specific) = id_maps[class].get(&id) {
+                    lock.ids[class].insert(key, *specific);
+                }
+            }
+        }
+        lock
+    }
+}
+
+// a json file listing every ID given out in this build and where it came from
+pub fn id_map_json(origins: &IdOrigins, id_maps: &IdMaps) -> String {
+    let mut out = String::from("{\n");
+    for (class, name) in ["groups", "colors", "blocks", "items"].iter().enumerate() {
+        let mut ids = id_maps[class]
+            .iter()
+            .collect::<Vec<(&ArbitraryId, &SpecificId)>>();
+        // arbitrary IDs can share a specific ID, so sort by both
+        ids.sort_by_key(|(a, s)| (**s, **a));
+        let ids = ids
+            .iter()
+            .map(|(a, s)| (**s, origins[class].get(a)))
+            .collect::<Vec<(SpecificId, Option<&IdOrigin>)>>();
+
+        out += &format!("  \"{}\": [", name);
+        out += &ids
+            .iter()
+            .map(|(id, origin)| {
+                // IDs without an origin are made by the compiler (like for if statements)
+                let (name, site) = match origin {
+                    Some(o) => (o.name.as_deref().map(json_str), Some(json_str(&o.site))),
+                    None => (None, None),
+                };
+                format!(
+                    "\n    {{\"id\": {}, \"name\": {}, \"site\": {}}}",
+                    id,
+                    name.unwrap_or_else(|| "null".to_string()),
+                    site.unwrap_or_else(|| "null".to_string())
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+        out += if ids.is_empty() { "]" } else { "\n  ]" };
+        out += if class < 3 { ",\n" } else { "\n" };
+    }
+    out + "}\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ObjectMode;
+    use crate::builtin::Id;
+    use crate::levelstring::{append_objects, ObjParam};
+    use crate::test_util::*;
+
+    // for each variable, a block in a group stored in it and a move trigger moving the group
+    fn build(names: &[&str], lock: &Lockfile) -> (Lockfile, HashMap<String, SpecificId>) {
+        let mut origins = IdOrigins::default();
+        let mut objects = Vec::new();
+        for (i, name) in names.iter().enumerate() {
+            let id = i as ArbitraryId + 1;
+            origins[0].insert(
+                id,
+                IdOrigin {
+                    site: format!("test.spwn:{}:1", i + 1),
+                    name: Some(name.to_string()),
+                },
+            );
+            objects.push(obj(
+                ObjectMode::Object,
+                vec![(1, ObjParam::Number(1.0)), (57, group(Id::Arbitrary(id)))],
+            ));
+            objects.push(obj(
+                ObjectMode::Trigger,
+                vec![(1, ObjParam::Number(901.0)), (51, group(Id::Arbitrary(id)))],
+            ));
+        }
+        let (_, _, id_maps) = append_objects(
+            objects,
+            "",
+            None,
+            &lock.preferred_ids(&origins),
+            &Default::default(),
+            &Default::default(),
+        )
+        .unwrap();
+        let ids = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.to_string(), id_maps[0][&(i as ArbitraryId + 1)]))
+            .collect();
+        (Lockfile::from_build(&origins, &id_maps), ids)
+    }
+
+    fn assert_unique(ids: &HashMap<String, SpecificId>) {
+        let mut specific = ids.values().collect::<Vec<&SpecificId>>();
+        specific.sort();
+        specific.dedup();
+        assert_eq!(specific.len(), ids.len(), "{:?}", ids);
+    }
+
+    #[test]
+    fn ids_stay_the_same_between_builds() {
+        let (lock, first) = build(&["a", "b", "c"], &Lockfile::default());
+        assert_unique(&first);
+
+        // a new variable before the others changes every arbitrary id
+        let (_, second) = build(&["new", "a", "c", "b"], &lock);
+        assert_unique(&second);
+        for name in &["a", "b", "c"] {
+            assert_eq!(first[*name], second[*name]);
+        }
+
+        // reading the written lockfile gives the same ids
+        let path = std::env::temp_dir().join("spwn_lockfile_test.lock");
+        lock.write(&path).unwrap();
+        assert_eq!(Lockfile::read(&path).unwrap(), lock);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn lockfile_ids_are_not_given_out_twice() {
+        let mut lock = Lockfile::default();
+        lock.ids[0].insert("a#0".to_string(), 5);
+        lock.ids[0].insert("b#0".to_string(), 5);
+        let (_, ids) = build(&["a", "b", "c"], &lock);
+        assert_unique(&ids);
+        assert_eq!(ids["a"], 5);
+    }
+
+    #[test]
+    fn id_maps() {
+        let mut origins = IdOrigins::default();
+        origins[0].insert(
+            1,
+            IdOrigin {
+                site: "lib/a.spwn:3:5".to_string(),
+                name: Some("hidden_group".to_string()),
+            },
+        );
+        origins[3].insert(
+            1,
+            IdOrigin {
+                site: "test.spwn:1:1".to_string(),
+                name: None,
+            },
+        );
+        let mut id_maps = IdMaps::default();
+        id_maps[0].insert(1, 12);
+        // made by the compiler
+        id_maps[0].insert(2, 3);
+        id_maps[3].insert(1, 1);
+        assert_eq!(
+            id_map_json(&origins, &id_maps),
+            r#"{
+  "groups": [
+    {"id": 3, "name": null, "site": null},
+    {"id": 12, "name": "hidden_group", "site": "lib/a.spwn:3:5"}
+  ],
+  "colors": [],
+  "blocks": [],
+  "items": [
+    {"id": 1, "name": null, "site": "test.spwn:1:1"}
+  ]
+}
+"#
+        );
+    }
+
+    #[test]
+    fn origins_from_scripts() {
+        let globals = compile("hidden_group = ?g\nlet c = ?c", Vec::new()).unwrap();
+        let mut names = globals.id_origins[0]
+            .values()
+            .chain(globals.id_origins[1].values())
+            .map(|o| o.name.clone())
+            .collect::<Vec<Option<String>>>();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![Some("c".to_string()), Some("hidden_group".to_string())]
+        );
+    }
+}
@@ -1,4 +1,6 @@
 //#![feature(arbitrary_enum_discriminant)]
+// errors carry the compiler info of where they happened
+#![allow(clippy::result_large_err)]
 
 mod ast;
 mod builtin;
@@ -8,8 +10,12 @@ mod compiler_types;
 mod documentation;
 mod fmt;
 mod globals;
+mod icalgebra;
+mod inspect;
 mod levelstring;
+mod lockfile;
 mod parser;
+mod simulate;
 mod value;
 
 mod context;
@@ -21,14 +27,18 @@ mod context;
 )]
 mod editorlive;
 mod optimize;
+mod target;
+#[cfg(test)]
+mod test_util;
 mod value_storage;
 
 use optimize::optimize;
 
 use parser::*;
 
+use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use std::fs;
 
@@ -61,6 +71,217 @@ fn eprint_with_color(text: &str, color: Color) {
     stdout.set_color(&ColorSpec::new()).unwrap();
 }
 
+// the value given to a flag, either as `--flag=value` or as the next argument.
+// another flag in its place means the value was forgotten (negative numbers are fine)
+fn flag_value(
+    args_iter: &mut std::slice::Iter<String>,
+    inline: Option<&str>,
+    flag: &str,
+    expected: &str,
+) -> Result<String, String> {
+    let is_flag = |v: &str| {
+        v.starts_with('-') && !v[1..].starts_with(|c: char| c.is_ascii_digit() || c == '.')
+    };
+    match inline {
+        Some("") => Err(format!("Expected {} after {}=", expected, flag)),
+        Some(value) => Ok(value.to_string()),
+        None => match args_iter.next() {
+            Some(value) if !is_flag(value) => Ok(value.clone()),
+            Some(value) => Err(format!(
+                "Expected {} after {}, found the option {}",
+                expected, flag, value
+            )),
+            None => Err(format!("Expected {} after {}", expected, flag)),
+        },
+    }
+}
+
+fn get_save_path(save_file: Option<String>) -> PathBuf {
+    if let Some(save_file) = save_file {
+        PathBuf::from(save_file)
+    } else if cfg!(target_os = "windows") {
+        PathBuf::from(std::env::var("localappdata").expect("No local app data"))
+            .join("GeometryDash/CCLocalLevels.dat")
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(std::env::var("HOME").expect("No home directory"))
+            .join("Library/Application Support/GeometryDash/CCLocalLevels.dat")
+    } else if cfg!(target_os = "linux") {
+        PathBuf::from(std::env::var("HOME").expect("No home directory"))
+            .join(".steam/steam/steamapps/compatdata/322170/pfx/drive_c/users/steamuser/Local Settings/Application Data/GeometryDash/CCLocalLevels.dat")
+    } else {
+        panic!("Unsupported operating system");
+    }
+}
+
+fn read_level(gd_path: &Path, level_name: Option<String>) -> Result<String, String> {
+    let file_content = match fs::read(gd_path) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Problem reading savefile: {}", e)),
+    };
+    levelstring::get_level_string(file_content, level_name)
+}
+
+// reads a level string from a save file, a .gmd file or a raw level string dump
+fn read_level_source(path: &Path, level_name: Option<String>) -> Result<String, String> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("dat") => read_level(path, level_name),
+        Some("gmd") => match fs::read_to_string(path) {
+            Ok(c) => levelstring::get_gmd_level_string(&c),
+            Err(e) => Err(format!("Problem reading {}: {}", path.to_string_lossy(), e)),
+        },
+        _ => match fs::read_to_string(path) {
+            Ok(c) => {
+                // encoded level strings are gzipped, so they always start like this
+                if c.trim().starts_with("H4sI") {
+                    levelstring::decode_level_string(&c)
+                } else {
+                    Ok(c.trim().to_string())
+                }
+            }
+            Err(e) => Err(format!("Problem reading {}: {}", path.to_string_lossy(), e)),
+        },
+    }
+}
+
+fn get_included_paths() -> Vec<PathBuf> {
+    vec![
+        std::env::current_dir().expect("Cannot access current directory"),
+        std::env::current_exe()
+            .expect("Cannot access directory of executable")
+            .parent()
+            .expect("Executable must be in some directory")
+            .to_path_buf(),
+    ]
+}
+
+// names for object keys, from the obj_props table in the standard library
+fn get_obj_props() -> HashMap<u16, String> {
+    match inspect::get_obj_prop_names(get_included_paths()) {
+        Ok(p) => p,
+        Err(_) => {
+            eprint_with_color(
+                "Could not read obj_props from the standard library, showing key numbers instead",
+                Color::Yellow,
+            );
+            HashMap::new()
+        }
+    }
+}
+
+// splits `--flag=value` into the flag and its value
+fn split_flag(arg: &str) -> (&str, Option<&str>) {
+    match arg.split_once('=') {
+        Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+        _ => (arg, None),
+    }
+}
+
+// runs the triggers of a level, and checks the expectations in the timeline.
+// returns false if any of them weren't met
+fn simulate_level(
+    ls: &str,
+    timeline_path: Option<&Path>,
+    until: Option<f64>,
+    show_log: bool,
+) -> Result<bool, String> {
+    let (_, objects) = levelstring::parse_level_string(ls)?;
+    let timeline = match timeline_path {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(t) => simulate::parse_timeline(&t)?,
+            Err(e) => return Err(format!("Problem reading {}: {}", path.to_string_lossy(), e)),
+        },
+        None => simulate::parse_timeline("")?,
+    };
+
+    // by default, run until a few seconds after the last thing in the timeline
+    let last = timeline
+        .inputs
+        .iter()
+        .map(|(t, _)| *t)
+        .chain(timeline.expectations.iter().map(|(t, _, _)| *t))
+        .max()
+        .unwrap_or(0);
+    let until = match until {
+        Some(u) => (u * 1000.0).round() as u64,
+        None => last + 10000,
+    };
+
+    print_with_color("\nSimulating...", Color::Cyan);
+    let mut sim = simulate::Simulation::new(objects, &timeline.inputs);
+    let failed = simulate::check_timeline(&mut sim, &timeline)?;
+    sim.run_until(until)?;
+
+    if show_log {
+        for (time, effect) in &sim.log {
+            let text = match effect {
+                simulate::Effect::Output(i) => {
+                    let obj = &sim.objects()[*i];
+                    format!(
+                        "trigger {} ran",
+                        obj.params
+                            .get(&1)
+                            .map(|id| id.to_string())
+                            .unwrap_or_default()
+                    )
+                }
+                simulate::Effect::ItemChanged(item, value, _) => {
+                    format!("{}i = {}", levelstring::ObjParam::Item(*item), value)
+                }
+                simulate::Effect::Toggled(group, on, _) => format!(
+                    "{}g toggled {}",
+                    levelstring::ObjParam::Group(*group),
+                    if *on { "on" } else { "off" }
+                ),
+            };
+            print_with_color(
+                &format!("[{:.3}s] {}", *time as f64 / 1000.0, text),
+                Color::White,
+            );
+        }
+    }
+
+    let mut items = sim
+        .log
+        .iter()
+        .filter_map(|(_, e)| match e {
+            simulate::Effect::ItemChanged(item, _, _) => Some(*item),
+            _ => None,
+        })
+        .collect::<Vec<builtin::Item>>();
+    items.sort();
+    items.dedup();
+    for item in items {
+        print_with_color(
+            &format!(
+                "{}i = {}",
+                levelstring::ObjParam::Item(item),
+                sim.item(item)
+            ),
+            Color::White,
+        );
+    }
+    if !sim.finished() {
+        print_with_color(
+            &format!(
+                "Stopped at {}s, there are still triggers waiting",
+                until as f64 / 1000.0
+            ),
+            Color::Yellow,
+        );
+    }
+
+    for f in &failed {
+        eprint_with_color(f, Color::Red);
+    }
+    if !timeline.expectations.is_empty() && failed.is_empty() {
+        print_with_color(
+            &format!("All {} expectations met", timeline.expectations.len()),
+            Color::Green,
+        );
+    }
+    Ok(failed.is_empty())
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
     let mut args_iter = args.iter();
@@ -84,37 +305,192 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     };
 
                     let mut gd_enabled = true;
-                    let mut opti_enabled = true;
+                    let mut opt_passes = optimize::OptPasses::default();
+                    let mut pass_toggles = Vec::new();
                     let mut compile_only = false;
                     let mut level_name = None;
                     let mut live_editor = false;
+                    let mut output_format = "raw".to_string();
+                    let mut output_path = None;
+                    let mut lockfile_path = None;
+                    let mut id_map_path = None;
+                    let mut source_map_path = None;
+                    let mut cost_report = false;
+                    let mut reserved = levelstring::ReservedIds::default();
+                    let mut target = target::Target::default();
+                    let mut trigger_table = None;
+                    let mut simulate_timeline = None;
+                    let mut verify_optimizer = false;
+                    let mut emit = Vec::new();
+                    let mut layout = levelstring::Layout::default();
 
                     let mut save_file = None;
-                    let mut included_paths = vec![
-                        std::env::current_dir().expect("Cannot access current directory"),
-                        std::env::current_exe()
-                            .expect("Cannot access directory of executable")
-                            .parent()
-                            .expect("Executable must be in some directory")
-                            .to_path_buf(),
-                    ];
+                    let mut included_paths = get_included_paths();
                     //change to current_exe before release (from current_dir)
 
                     while let Some(arg) = args_iter.next() {
-                        match arg.as_ref() {
+                        let (arg, value) = split_flag(arg);
+                        match arg {
                             "--console-output" | "-c" => gd_enabled = false,
                             "--no-level" | "-l" => {
                                 gd_enabled = false;
                                 compile_only = true;
                             }
-                            "--no-optimize" | "-o" => opti_enabled = false,
-                            "--level-name" | "-n" => level_name = args_iter.next().cloned(),
+                            "--no-optimize" | "-O0" => {
+                                opt_passes = optimize::OptPasses::from_level(0)?
+                            }
+                            "-O1" => opt_passes = optimize::OptPasses::from_level(1)?,
+                            "-O2" => opt_passes = optimize::OptPasses::from_level(2)?,
+                            "--opt-pass" => pass_toggles.push(flag_value(
+                                &mut args_iter,
+                                value,
+                                arg,
+                                "a list of passes (like +merge-triggers,-instant-count)",
+                            )?),
+                            "--output-format" | "-f" => {
+                                output_format = flag_value(
+                                    &mut args_iter,
+                                    value,
+                                    arg,
+                                    "an output format (raw, encoded or objects)",
+                                )?;
+                                if !["raw", "encoded", "objects"].contains(&output_format.as_str())
+                                {
+                                    return Err(Box::from(format!(
+                                        "Unknown output format: {} (expected raw, encoded or objects)",
+                                        output_format
+                                    )));
+                                }
+                                gd_enabled = false;
+                            }
+                            "--output" | "-o" => {
+                                output_path = Some(PathBuf::from(flag_value(
+                                    &mut args_iter,
+                                    value,
+                                    arg,
+                                    "a path",
+                                )?));
+                                gd_enabled = false;
+                            }
+                            "--level-name" | "-n" => {
+                                level_name =
+                                    Some(flag_value(&mut args_iter, value, arg, "a level name")?)
+                            }
                             "--live-editor" | "-e" => live_editor = true,
-                            "--save-file" | "-s" => save_file = args_iter.next().cloned(),
+                            "--save-file" | "-s" => {
+                                save_file = Some(flag_value(&mut args_iter, value, arg, "a path")?)
+                            }
+                            "--lockfile" => {
+                                lockfile_path = Some(PathBuf::from(flag_value(
+                                    &mut args_iter,
+                                    value,
+                                    arg,
+                                    "a path",
+                                )?))
+                            }
+                            "--reserve" => {
+                                reserved.add(&flag_value(&mut args_iter, value, arg, "ranges")?)?
+                            }
+                            "--target" => {
+                                target = target::Target::from_name(&flag_value(
+                                    &mut args_iter,
+                                    value,
+                                    arg,
+                                    "a target",
+                                )?)?
+                            }
+                            "--trigger-table" => {
+                                trigger_table = Some(PathBuf::from(flag_value(
+                                    &mut args_iter,
+                                    value,
+                                    arg,
+                                    "a path",
+                                )?))
+                            }
+                            "--simulate" => {
+                                simulate_timeline = Some(PathBuf::from(flag_value(
+                                    &mut args_iter,
+                                    value,
+                                    arg,
+                                    "a path",
+                                )?))
+                            }
+                            "--verify-optimizer" => verify_optimizer = true,
+                            "--layout" => {
+                                layout.strategy = levelstring::Layout::parse_strategy(&flag_value(
+                                    &mut args_iter,
+                                    value,
+                                    arg,
+                                    "a layout",
+                                )?)?
+                            }
+                            "--debug-labels" => layout.debug_labels = true,
+                            "--layout-origin" => {
+                                layout.origin = levelstring::Layout::parse_origin(&flag_value(
+                                    &mut args_iter,
+                                    value,
+                                    arg,
+                                    "a position (like -3000,-30) or after-level",
+                                )?)?
+                            }
+                            "--editor-layer" => {
+                                let layer =
+                                    flag_value(&mut args_iter, value, arg, "an editor layer")?;
+                                layout.editor_layer = match layer.parse() {
+                                    Ok(n) => Some(n),
+                                    Err(_) => {
+                                        return Err(Box::from(format!(
+                                            "Expected an editor layer number, found {}",
+                                            layer
+                                        )))
+                                    }
+                                };
+                            }
+                            "--emit" => {
+                                let output = flag_value(
+                                    &mut args_iter,
+                                    value,
+                                    arg,
+                                    "an output (like trigger-graph=[file])",
+                                )?;
+                                match output.split_once('=') {
+                                    Some((kind, path))
+                                        if kind == "trigger-graph" || kind == "trigger-graph-before" =>
+                                    {
+                                        emit.push((kind.to_string(), PathBuf::from(path)))
+                                    }
+                                    _ => {
+                                        return Err(Box::from(format!(
+                                            "Unknown output {} (expected trigger-graph=[file] or trigger-graph-before=[file])",
+                                            output
+                                        )))
+                                    }
+                                }
+                            }
+                            "--cost-report" => cost_report = true,
+                            "--source-map" => {
+                                source_map_path = Some(PathBuf::from(flag_value(
+                                    &mut args_iter,
+                                    value,
+                                    arg,
+                                    "a path",
+                                )?))
+                            }
+                            "--id-map" => {
+                                id_map_path = Some(PathBuf::from(flag_value(
+                                    &mut args_iter,
+                                    value,
+                                    arg,
+                                    "a path",
+                                )?))
+                            }
                             "--included-path" | "-i" => included_paths.push({
-                                let path = PathBuf::from(
-                                    args_iter.next().cloned().expect("No path provided"),
-                                );
+                                let path = PathBuf::from(flag_value(
+                                    &mut args_iter,
+                                    value,
+                                    arg,
+                                    "a path",
+                                )?);
                                 if path.exists() {
                                     path
                                 } else {
@@ -125,6 +501,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         };
                     }
 
+                    for toggle in pass_toggles {
+                        for pass in toggle.split(',') {
+                            opt_passes.toggle(pass)?;
+                        }
+                    }
+
+                    if let Some(path) = trigger_table {
+                        let table = fs::read_to_string(&path)?;
+                        target.triggers.extend(target::parse_trigger_table(&table)?);
+                    }
+
                     print_with_color("Parsing ...", Color::Green);
                     let unparsed = fs::read_to_string(script_path.clone())?;
 
@@ -140,6 +527,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     for tag in tags {
                         match tag.0.as_str() {
                             "console_output" => gd_enabled = false,
+                            "reserve" => {
+                                for arg in &tag.1 {
+                                    match &arg.value.values[0].value.body {
+                                        ast::ValueBody::Str(s) => reserved.add(s)?,
+                                        _ => {
+                                            return Err(Box::from(
+                                                "The reserve attribute takes strings like \"100..200g\"",
+                                            ))
+                                        }
+                                    }
+                                }
+                            }
                             "no_level" => {
                                 gd_enabled = false;
                                 compile_only = true;
@@ -148,30 +547,64 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                     }
 
+                    let namespace = match compiler::script_namespace(&notes, &script_path) {
+                        Ok(n) => n,
+                        Err(e) => {
+                            eprint_with_color(&format!("{}\n", e), Color::Red);
+                            std::process::exit(ERROR_EXIT_CODE);
+                        }
+                    };
+
                     let gd_path = if gd_enabled {
-                        Some(if save_file != None {
-                            PathBuf::from(save_file.expect("what"))
-                        } else if cfg!(target_os = "windows") {
-                            PathBuf::from(std::env::var("localappdata").expect("No local app data"))
-                                .join("GeometryDash/CCLocalLevels.dat")
-                        } else if cfg!(target_os = "macos") {
-                            PathBuf::from(std::env::var("HOME").expect("No home directory"))
-                                .join("Library/Application Support/GeometryDash/CCLocalLevels.dat")
-                        } else if cfg!(target_os = "linux") {
-                            PathBuf::from(std::env::var("HOME").expect("No home directory"))
-                                .join(".steam/steam/steamapps/compatdata/322170/pfx/drive_c/users/steamuser/Local Settings/Application Data/GeometryDash/CCLocalLevels.dat")
-                        } else {
-                            panic!("Unsupported operating system");
-                        })
+                        Some(get_save_path(save_file))
                     } else {
                         None
                     };
 
+                    let level_string = match &gd_path {
+                        Some(gd_path) if !compile_only => {
+                            print_with_color("Reading savefile...", Color::Cyan);
+                            let mut level_string = match read_level(gd_path, level_name.clone()) {
+                                Ok(s) => s,
+                                Err(e) => {
+                                    eprint_with_color(
+                                        &format!("Error reading level:\n{}", e),
+                                        Color::Red,
+                                    );
+
+                                    std::process::exit(ERROR_EXIT_CODE);
+                                }
+                            };
+                            if let Err(e) = levelstring::remove_spwn_objects(
+                                &mut level_string,
+                                Some(&namespace),
+                            ) {
+                                eprint_with_color(
+                                    &format!("Error reading level:\n{}", e),
+                                    Color::Red,
+                                );
+                                std::process::exit(ERROR_EXIT_CODE);
+                            }
+                            level_string
+                        }
+                        _ => String::new(),
+                    };
+
+                    // the objects that are already in the level, for $.level_objects()
+                    let level_objects = match levelstring::parse_level_string(&level_string) {
+                        Ok((_, objects)) => objects,
+                        Err(e) => {
+                            eprint_with_color(&format!("Error reading level:\n{}", e), Color::Red);
+                            std::process::exit(ERROR_EXIT_CODE);
+                        }
+                    };
+
                     let mut compiled = match compiler::compile_spwn(
                         statements,
                         script_path,
                         included_paths,
                         notes,
+                        level_objects,
                     ) {
                         Err(err) => {
                             eprint_with_color(&format!("{}\n", err), Color::White);
@@ -181,49 +614,200 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     };
 
                     if !compile_only {
-                        let level_string = if let Some(gd_path) = &gd_path {
-                            print_with_color("Reading savefile...", Color::Cyan);
-                            let mut file = fs::File::open(gd_path)?;
-                            let mut file_content = Vec::new();
-                            use std::io::Read;
-                            file.read_to_end(&mut file_content)
-                                .expect("Problem reading savefile");
-                            let mut level_string = match levelstring::get_level_string(
-                                file_content,
-                                level_name.clone(),
-                            ) {
-                                Ok(s) => s,
-                                Err(e) => {
-                                    eprint_with_color(
-                                        &format!("Error reading level:\n{}", e),
-                                        Color::Red,
-                                    );
+                        target.triggers.extend(compiled.trigger_table.clone());
 
-                                    std::process::exit(ERROR_EXIT_CODE);
-                                }
-                            };
-                            if level_string.is_empty() {}
-                            levelstring::remove_spwn_objects(&mut level_string);
-                            level_string
-                        } else {
-                            String::new()
+                        let write_graph = |kind: &str,
+                                           func_ids: &[compiler_types::FunctionId]|
+                         -> std::io::Result<()> {
+                            for (_, path) in emit.iter().filter(|(k, _)| k == kind) {
+                                fs::write(path, optimize::trigger_graph(func_ids, &target))?;
+                                print_with_color(
+                                    &format!("Trigger graph written to {}", path.to_string_lossy()),
+                                    Color::White,
+                                );
+                            }
+                            Ok(())
                         };
+                        write_graph("trigger-graph-before", &compiled.func_ids)?;
+
                         let has_stuff = compiled.func_ids.iter().any(|x| !x.obj_list.is_empty());
-                        if opti_enabled && has_stuff {
+                        if has_stuff {
                             print_with_color("Optimizing triggers...", Color::Cyan);
-                            compiled.func_ids = optimize(compiled.func_ids, compiled.closed_groups);
+                            // the read/write order fix is needed for the level to work at all,
+                            // so the reference for --verify-optimizer still has that done
+                            let reference = if verify_optimizer {
+                                Some(
+                                    optimize(
+                                        compiled.func_ids.clone(),
+                                        compiled.closed_groups,
+                                        &target,
+                                        optimize::OptPasses::from_level(0)?,
+                                    )
+                                    .0,
+                                )
+                            } else {
+                                None
+                            };
+                            let (func_ids, reports) = optimize(
+                                compiled.func_ids,
+                                compiled.closed_groups,
+                                &target,
+                                opt_passes,
+                            );
+                            compiled.func_ids = func_ids;
+                            for report in reports {
+                                print_with_color(
+                                    &format!(
+                                        "{}: {:+} triggers, {:+} groups",
+                                        report.name,
+                                        -report.triggers_removed,
+                                        -report.groups_removed
+                                    ),
+                                    Color::White,
+                                );
+                            }
+
+                            if let Some(reference) = reference {
+                                let mut before =
+                                    levelstring::apply_fn_ids(&reference, &Default::default(), &[]);
+                                before.extend(compiled.objects.iter().cloned());
+                                let mut after = levelstring::apply_fn_ids(
+                                    &compiled.func_ids,
+                                    &Default::default(),
+                                    &[],
+                                );
+                                after.extend(compiled.objects.iter().cloned());
+
+                                let mut traces = simulate::default_traces(&before);
+                                if let Some(path) = &simulate_timeline {
+                                    let timeline = match fs::read_to_string(path) {
+                                        Ok(t) => simulate::parse_timeline(&t)?,
+                                        Err(e) => {
+                                            return Err(Box::from(format!(
+                                                "Problem reading {}: {}",
+                                                path.to_string_lossy(),
+                                                e
+                                            )))
+                                        }
+                                    };
+                                    traces.push(simulate::Trace {
+                                        name: format!("the timeline in {}", path.to_string_lossy()),
+                                        inputs: timeline.inputs,
+                                    });
+                                }
+
+                                print_with_color("Verifying optimizer...", Color::Cyan);
+                                match simulate::compare_levels(
+                                    before,
+                                    after,
+                                    compiled.closed_groups,
+                                    &traces,
+                                )? {
+                                    Some(divergence) => {
+                                        eprint_with_color(&format!("{}\n", divergence), Color::Red);
+                                        std::process::exit(ERROR_EXIT_CODE);
+                                    }
+                                    None => print_with_color(
+                                        &format!("Optimizer verified on {} traces", traces.len()),
+                                        Color::Green,
+                                    ),
+                                }
+                            }
                         }
 
-                        let mut objects = levelstring::apply_fn_ids(&compiled.func_ids);
+                        write_graph("trigger-graph", &compiled.func_ids)?;
+
+                        // triggers shouldn't be put on top of the level, or the objects the script added
+                        let mut taken = compiled.level_objects.clone();
+                        taken.extend(compiled.objects.iter().cloned());
+                        let mut objects =
+                            levelstring::apply_fn_ids(&compiled.func_ids, &layout, &taken);
 
                         objects.extend(compiled.objects);
 
+                        // runs on every object, since the groups of the ones that aren't
+                        // triggers can't be shared
+                        let recycled = if opt_passes.recycle_groups {
+                            let recycled = optimize::recycle_groups(&mut objects, &target);
+                            print_with_color(
+                                &format!(
+                                    "recycle-groups: +0 triggers, {:+} groups",
+                                    -(recycled.len() as i64)
+                                ),
+                                Color::White,
+                            );
+                            recycled
+                        } else {
+                            Vec::new()
+                        };
+
+                        for warning in target.check_objects(&objects) {
+                            print_with_color(&warning, Color::Yellow);
+                        }
+
                         print_with_color(&format!("{} objects added", objects.len()), Color::White);
 
-                        let (new_ls, used_ids) =
-                            levelstring::append_objects(objects, &level_string)?;
+                        if let Some(path) = &source_map_path {
+                            fs::write(
+                                path,
+                                levelstring::source_map_json(&objects, &compiled.path),
+                            )?;
+                            print_with_color(
+                                &format!("Source map written to {}", path.to_string_lossy()),
+                                Color::White,
+                            );
+                        }
+
+                        let lock = match &lockfile_path {
+                            Some(path) => lockfile::Lockfile::read(path)?,
+                            None => Default::default(),
+                        };
+                        let preferred_ids = lock.preferred_ids(&compiled.id_origins);
+
+                        // only objects written into a level get a namespace
+                        let namespace = gd_path.as_ref().map(|_| namespace.as_str());
+                        // the ids are replaced when the objects are added
+                        let report_objects = if cost_report {
+                            Some(objects.clone())
+                        } else {
+                            None
+                        };
+
+                        let (new_ls, used_ids, mut id_maps) = levelstring::append_objects(
+                            objects,
+                            &level_string,
+                            namespace,
+                            &preferred_ids,
+                            &reserved,
+                            &target,
+                        )?;
+                        optimize::map_recycled_groups(&mut id_maps, &recycled);
+
+                        if let Some(path) = &lockfile_path {
+                            lockfile::Lockfile::from_build(&compiled.id_origins, &id_maps)
+                                .write(path)?;
+                        }
+
+                        if let Some(path) = &id_map_path {
+                            fs::write(path, lockfile::id_map_json(&compiled.id_origins, &id_maps))?;
+                            print_with_color(
+                                &format!("ID map written to {}", path.to_string_lossy()),
+                                Color::White,
+                            );
+                        }
+
+                        if let Some(report_objects) = &report_objects {
+                            print_with_color("\nCost report:", Color::Magenta);
+                            print_with_color(
+                                &levelstring::cost_report(report_objects, &id_maps, &compiled.path),
+                                Color::White,
+                            );
+                        }
 
                         print_with_color("\nLevel:", Color::Magenta);
+                        if let Some(namespace) = namespace {
+                            print_with_color(&format!("namespace \"{}\"", namespace), Color::White);
+                        }
                         for (i, len) in used_ids.iter().enumerate() {
                             if *len > 0 {
                                 print_with_color(
@@ -236,6 +820,36 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 );
                             }
                         }
+                        if let Some(path) = &simulate_timeline {
+                            if !simulate_level(&new_ls, Some(path), None, false)? {
+                                std::process::exit(ERROR_EXIT_CODE);
+                            }
+                        }
+
+                        let settings = &compiled.level_settings;
+                        if gd_path.is_none()
+                            && (settings.name.is_some()
+                                || settings.description.is_some()
+                                || settings.song.is_some()
+                                || settings.custom_song.is_some())
+                        {
+                            print_with_color(
+                                "Level name, description and song can only be set when writing to a save file",
+                                Color::Yellow,
+                            );
+                        }
+                        let level_string = if live_editor {
+                            if settings.has_header_settings() {
+                                print_with_color(
+                                    "Start settings and level colors can not be pasted into the editor",
+                                    Color::Yellow,
+                                );
+                            }
+                            level_string
+                        } else {
+                            levelstring::apply_level_settings(&level_string, settings)
+                        };
+
                         //println!("level_string: {}", level_string);
                         if live_editor {
                             match editor_paste(&new_ls) {
@@ -260,6 +874,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                         level_string,
                                         gd_path,
                                         level_name,
+                                        &compiled.level_settings,
                                     )?;
 
                                     print_with_color(
@@ -268,7 +883,25 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     );
                                 }
 
-                                None => println!("Output: {}", new_ls),
+                                None => {
+                                    // level_string only holds the header here
+                                    let new_ls = level_string + &new_ls;
+                                    let output = match output_format.as_str() {
+                                        "encoded" => levelstring::encode_level_string(&new_ls),
+                                        "objects" => levelstring::level_string_to_json(&new_ls)?,
+                                        _ => new_ls,
+                                    };
+                                    match &output_path {
+                                        Some(path) => {
+                                            fs::write(path, output)?;
+                                            print_with_color(
+                                                &format!("Written to {}", path.to_string_lossy()),
+                                                Color::Green,
+                                            );
+                                        }
+                                        None => println!("Output: {}", output),
+                                    }
+                                }
                             };
                         }
                     };
@@ -279,6 +912,312 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     Ok(())
                 }
 
+                "inspect" => {
+                    let mut save_file = None;
+                    let mut level_name = None;
+                    let mut show_triggers = false;
+
+                    while let Some(arg) = args_iter.next() {
+                        let (arg, value) = split_flag(arg);
+                        match arg {
+                            "--save-file" | "-s" => {
+                                save_file = Some(flag_value(&mut args_iter, value, arg, "a path")?)
+                            }
+                            "--level-name" | "-n" => {
+                                level_name =
+                                    Some(flag_value(&mut args_iter, value, arg, "a level name")?)
+                            }
+                            "--triggers" | "-t" => show_triggers = true,
+                            a => return Err(Box::from(format!("Unknown inspect option: {}", a))),
+                        };
+                    }
+
+                    let level_string =
+                        match read_level_source(&get_save_path(save_file), level_name) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                eprint_with_color(
+                                    &format!("Error reading level:\n{}", e),
+                                    Color::Red,
+                                );
+                                std::process::exit(ERROR_EXIT_CODE);
+                            }
+                        };
+
+                    let obj_props = get_obj_props();
+
+                    if let Err(e) = inspect::inspect_level(&level_string, show_triggers, &obj_props)
+                    {
+                        eprint_with_color(&format!("Error reading level:\n{}", e), Color::Red);
+                        std::process::exit(ERROR_EXIT_CODE);
+                    }
+
+                    Ok(())
+                }
+
+                "clean" => {
+                    let mut source = None;
+                    let mut save_file = None;
+                    let mut level_name = None;
+                    let mut live_editor = false;
+                    let mut namespace = None;
+
+                    while let Some(arg) = args_iter.next() {
+                        let (arg, value) = split_flag(arg);
+                        match arg {
+                            "--save-file" | "-s" => {
+                                save_file = Some(flag_value(&mut args_iter, value, arg, "a path")?)
+                            }
+                            "--level-name" | "-n" => {
+                                level_name =
+                                    Some(flag_value(&mut args_iter, value, arg, "a level name")?)
+                            }
+                            "--live-editor" | "-e" => live_editor = true,
+                            "--namespace" => {
+                                namespace =
+                                    Some(flag_value(&mut args_iter, value, arg, "a namespace")?)
+                            }
+                            a if a.starts_with('-') => {
+                                return Err(Box::from(format!("Unknown clean option: {}", a)))
+                            }
+                            a => match source {
+                                None => source = Some(PathBuf::from(a)),
+                                Some(_) => {
+                                    return Err(Box::from(format!(
+                                        "Expected one level to clean, found another: {}",
+                                        a
+                                    )))
+                                }
+                            },
+                        };
+                    }
+
+                    if live_editor {
+                        return Err(Box::from(
+                            "The live editor can only paste objects, so it can't be cleaned. Use the save file or a .gmd file instead",
+                        ));
+                    }
+
+                    let path = match source {
+                        Some(p) => p,
+                        None => get_save_path(save_file),
+                    };
+                    let extension = path.extension().and_then(|e| e.to_str()).map(String::from);
+
+                    let level_string = match read_level_source(&path, level_name.clone()) {
+                        Ok(s) => s,
+                        Err(e) => {
+                            eprint_with_color(&format!("Error reading level:\n{}", e), Color::Red);
+                            std::process::exit(ERROR_EXIT_CODE);
+                        }
+                    };
+
+                    let (cleaned, removed, freed) =
+                        match inspect::clean_level(&level_string, namespace.as_deref()) {
+                            Ok(c) => c,
+                            Err(e) => {
+                                eprint_with_color(
+                                    &format!("Error reading level:\n{}", e),
+                                    Color::Red,
+                                );
+                                std::process::exit(ERROR_EXIT_CODE);
+                            }
+                        };
+                    print_with_color(&format!("{} objects removed", removed), Color::White);
+                    for (i, ids) in freed.iter().enumerate() {
+                        if !ids.is_empty() {
+                            println!(
+                                "{} {} freed: {}",
+                                ids.len(),
+                                ["groups", "colors", "block IDs", "item IDs"][i],
+                                inspect::format_id_list(ids)
+                            );
+                        }
+                    }
+                    if removed == 0 {
+                        print_with_color("No SPWN objects found, nothing to clean", Color::Green);
+                        return Ok(());
+                    }
+
+                    match extension.as_deref() {
+                        Some("dat") => {
+                            print_with_color("\nWriting back to savefile...", Color::Cyan);
+                            levelstring::encrypt_level_string(
+                                String::new(),
+                                cleaned,
+                                path,
+                                level_name,
+                                &Default::default(),
+                            )?;
+                            print_with_color(
+                                "Written to save. You can now open Geometry Dash again!",
+                                Color::Green,
+                            );
+                        }
+                        Some("gmd") => {
+                            let gmd = fs::read_to_string(&path)?;
+                            fs::write(&path, levelstring::set_gmd_level_string(&gmd, &cleaned)?)?;
+                            print_with_color(
+                                &format!("Written to {}", path.to_string_lossy()),
+                                Color::Green,
+                            );
+                        }
+                        _ => {
+                            // keep the level string in the format it was in
+                            let encoded = fs::read_to_string(&path)?.trim().starts_with("H4sI");
+                            let output = if encoded {
+                                levelstring::encode_level_string(&cleaned)
+                            } else {
+                                cleaned
+                            };
+                            fs::write(&path, output)?;
+                            print_with_color(
+                                &format!("Written to {}", path.to_string_lossy()),
+                                Color::Green,
+                            );
+                        }
+                    };
+
+                    Ok(())
+                }
+
+                "diff" => {
+                    let mut sources = Vec::new();
+                    let mut level_names = Vec::new();
+
+                    while let Some(arg) = args_iter.next() {
+                        let (arg, value) = split_flag(arg);
+                        match arg {
+                            "--level-name" | "-n" => level_names.push(flag_value(
+                                &mut args_iter,
+                                value,
+                                arg,
+                                "a level name",
+                            )?),
+                            a if a.starts_with('-') => {
+                                return Err(Box::from(format!("Unknown diff option: {}", a)))
+                            }
+                            a => sources.push(PathBuf::from(a)),
+                        };
+                    }
+
+                    if sources.len() != 2 {
+                        return Err(Box::from("Expected two levels to compare"));
+                    }
+
+                    // level names are given in the same order as the save files,
+                    // one name is used for both
+                    let mut level_names = level_names.into_iter();
+                    let mut last_name = None;
+                    let mut level_strings = Vec::new();
+                    for source in &sources {
+                        let level_name =
+                            if source.extension().and_then(|e| e.to_str()) == Some("dat") {
+                                if let Some(n) = level_names.next() {
+                                    last_name = Some(n);
+                                }
+                                last_name.clone()
+                            } else {
+                                None
+                            };
+                        match read_level_source(source, level_name) {
+                            Ok(ls) => level_strings.push(ls),
+                            Err(e) => {
+                                eprint_with_color(
+                                    &format!("Error reading {}:\n{}", source.to_string_lossy(), e),
+                                    Color::Red,
+                                );
+                                std::process::exit(ERROR_EXIT_CODE);
+                            }
+                        }
+                    }
+
+                    let obj_props = get_obj_props();
+
+                    if let Err(e) =
+                        inspect::diff_levels(&level_strings[0], &level_strings[1], &obj_props)
+                    {
+                        eprint_with_color(&format!("Error reading level:\n{}", e), Color::Red);
+                        std::process::exit(ERROR_EXIT_CODE);
+                    }
+
+                    Ok(())
+                }
+
+                "simulate" => {
+                    let mut source = None;
+                    let mut save_file = None;
+                    let mut level_name = None;
+                    let mut timeline = None;
+                    let mut until = None;
+                    let mut show_log = false;
+
+                    while let Some(arg) = args_iter.next() {
+                        let (arg, value) = split_flag(arg);
+                        match arg {
+                            "--save-file" | "-s" => {
+                                save_file = Some(flag_value(&mut args_iter, value, arg, "a path")?)
+                            }
+                            "--level-name" | "-n" => {
+                                level_name =
+                                    Some(flag_value(&mut args_iter, value, arg, "a level name")?)
+                            }
+                            "--timeline" | "-t" => {
+                                timeline = Some(PathBuf::from(flag_value(
+                                    &mut args_iter,
+                                    value,
+                                    arg,
+                                    "a path",
+                                )?))
+                            }
+                            "--until" => {
+                                let time =
+                                    flag_value(&mut args_iter, value, arg, "a time in seconds")?;
+                                until = match time.parse::<f64>() {
+                                    Ok(u) => Some(u),
+                                    Err(_) => {
+                                        return Err(Box::from(format!(
+                                            "Expected a time in seconds after --until, found {}",
+                                            time
+                                        )))
+                                    }
+                                }
+                            }
+                            "--log" => show_log = true,
+                            a if a.starts_with('-') => {
+                                return Err(Box::from(format!("Unknown simulate option: {}", a)))
+                            }
+                            a => match source {
+                                None => source = Some(PathBuf::from(a)),
+                                Some(_) => {
+                                    return Err(Box::from(format!(
+                                        "Expected one level to simulate, found another: {}",
+                                        a
+                                    )))
+                                }
+                            },
+                        };
+                    }
+
+                    let path = match source {
+                        Some(p) => p,
+                        None => get_save_path(save_file),
+                    };
+                    let level_string = match read_level_source(&path, level_name) {
+                        Ok(s) => s,
+                        Err(e) => {
+                            eprint_with_color(&format!("Error reading level:\n{}", e), Color::Red);
+                            std::process::exit(ERROR_EXIT_CODE);
+                        }
+                    };
+
+                    if !simulate_level(&level_string, timeline.as_deref(), until, show_log)? {
+                        std::process::exit(ERROR_EXIT_CODE);
+                    }
+
+                    Ok(())
+                }
+
                 "doc" => {
                     //use std::fs::File;
 
@@ -1,50 +1,25 @@
 use crate::ast::ObjectMode;
-use crate::builtin::{Block, Group, Id, Item};
+use crate::builtin::{ArbitraryId, Block, Group, Id, Item};
 use crate::compiler_types::FunctionId;
+use crate::icalgebra::simplify_instant_count_networks;
 use crate::levelstring::{GdObj, ObjParam};
-use std::collections::{HashMap, HashSet};
+use crate::lockfile::IdMaps;
+use crate::target::{Target, TriggerRole};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-enum TriggerRole {
-    // Spawn triggers have their own catagory
-    // because they can be combined by adding their delays
-    Spawn,
-
-    // Triggers like move and rotate, which have some output in the level
-    // and therefore cannot be optimized away
-    Output,
-
-    // Triggers that send a signal, but don't cause any side effects
-    Func,
-}
-
-fn get_role(obj_id: u16, hd: bool) -> TriggerRole {
-    match obj_id {
-        1268 => {
-            if hd {
-                TriggerRole::Func
-            } else {
-                TriggerRole::Spawn
-            }
-        }
-        1595 | 1611 | 1811 | 1815 | 1812 => TriggerRole::Func,
-        _ => TriggerRole::Output,
-    }
-}
-
-type ObjPtr = (usize, usize);
-//                                     triggers      connections in
-type TriggerNetwork = HashMap<Group, TriggerGang>;
+pub type ObjPtr = (usize, usize);
+// ordered by group, so the optimizer does the same thing every time
+pub type TriggerNetwork = BTreeMap<Group, TriggerGang>;
 
 #[derive(Debug, Clone)]
 // what do you mean? its a trigger gang!
-struct TriggerGang {
-    triggers: Vec<Trigger>,
-    connections_in: u32,
+pub struct TriggerGang {
+    pub triggers: Vec<Trigger>,
+    pub connections_in: u32,
 }
 
 impl TriggerGang {
-    fn new(triggers: Vec<Trigger>) -> Self {
+    pub fn new(triggers: Vec<Trigger>) -> Self {
         TriggerGang {
             triggers,
             connections_in: 0,
@@ -53,16 +28,16 @@ impl TriggerGang {
 }
 
 #[derive(Debug, Copy, Clone)]
-struct Trigger {
-    obj: ObjPtr,
-    role: TriggerRole,
-    order: usize,
-    deleted: bool,
-    optimized: bool,
+pub struct Trigger {
+    pub obj: ObjPtr,
+    pub role: TriggerRole,
+    pub order: usize,
+    pub deleted: bool,
+    pub optimized: bool,
 }
 
-struct Triggerlist<'a> {
-    list: &'a mut Vec<FunctionId>,
+pub struct Triggerlist<'a> {
+    pub list: &'a mut Vec<FunctionId>,
 }
 
 impl<'a> std::ops::Index<ObjPtr> for Triggerlist<'a> {
@@ -78,11 +53,302 @@ impl<'a> std::ops::IndexMut<ObjPtr> for Triggerlist<'a> {
     }
 }
 
-const NO_GROUP: Group = Group {
+pub const NO_GROUP: Group = Group {
     id: Id::Specific(0),
 };
 
-pub fn optimize(mut obj_in: Vec<FunctionId>, mut closed_group: u16) -> Vec<FunctionId> {
+// the passes that can be turned on and off, in the order they run.
+// fixing the read/write order is not in here, since nothing works without it
+pub const PASS_NAMES: &[&str] = &[
+    "instant-count",
+    "merge-triggers",
+    "dedupe-groups",
+    "recycle-groups",
+];
+
+// which optimization passes run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptPasses {
+    // rebuilds trees of instant count triggers from the smallest equivalent formula
+    pub instant_count: bool,
+    // removes spawn and function triggers that only pass the signal on
+    pub merge_triggers: bool,
+    // merges groups that have the same triggers
+    pub dedupe_groups: bool,
+    // gives groups that don't interfere with each other the same id
+    pub recycle_groups: bool,
+}
+
+impl Default for OptPasses {
+    fn default() -> Self {
+        OptPasses::from_level(2).unwrap()
+    }
+}
+
+impl OptPasses {
+    pub fn from_level(level: u8) -> Result<Self, String> {
+        match level {
+            0 => Ok(OptPasses {
+                instant_count: false,
+                merge_triggers: false,
+                dedupe_groups: false,
+                recycle_groups: false,
+            }),
+            1 => Ok(OptPasses {
+                instant_count: false,
+                merge_triggers: true,
+                dedupe_groups: false,
+                recycle_groups: false,
+            }),
+            2 => Ok(OptPasses {
+                instant_count: true,
+                merge_triggers: true,
+                dedupe_groups: true,
+                recycle_groups: true,
+            }),
+            a => Err(format!(
+                "Unknown optimization level {} (expected 0, 1 or 2)",
+                a
+            )),
+        }
+    }
+
+    // turns a pass on ("+name") or off ("-name")
+    pub fn toggle(&mut self, arg: &str) -> Result<(), String> {
+        let (on, name) = match arg.chars().next() {
+            Some('+') => (true, &arg[1..]),
+            Some('-') => (false, &arg[1..]),
+            _ => return Err(format!("Expected +pass or -pass, found {}", arg)),
+        };
+        match name {
+            "instant-count" => self.instant_count = on,
+            "merge-triggers" => self.merge_triggers = on,
+            "dedupe-groups" => self.dedupe_groups = on,
+            "recycle-groups" => self.recycle_groups = on,
+            "read-write-order" => {
+                if !on {
+                    return Err(
+                        "The read-write-order pass can't be turned off, since triggers would run in the wrong order without it"
+                            .to_string(),
+                    );
+                }
+            }
+            a => {
+                return Err(format!(
+                    "Unknown optimization pass {} (expected one of: {})",
+                    a,
+                    PASS_NAMES.join(", ")
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+// how many triggers and groups a pass removed (negative if it added some)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PassReport {
+    pub name: &'static str,
+    pub triggers_removed: i64,
+    pub groups_removed: i64,
+}
+
+// the number of triggers in the network, and the number of arbitrary groups they use
+fn network_size(network: &TriggerNetwork, objects: &Triggerlist, only_kept: bool) -> (i64, i64) {
+    let mut triggers = 0;
+    let mut groups = HashSet::new();
+    for gang in network.values() {
+        for trigger in &gang.triggers {
+            if only_kept && trigger.deleted {
+                continue;
+            }
+            triggers += 1;
+            for param in objects[trigger.obj].0.params.values() {
+                if let ObjParam::Group(g) = param {
+                    if let Id::Arbitrary(_) = g.id {
+                        groups.insert(*g);
+                    }
+                }
+            }
+        }
+    }
+    (triggers, groups.len() as i64)
+}
+
+pub fn optimize(
+    mut obj_in: Vec<FunctionId>,
+    mut closed_group: u16,
+    target: &Target,
+    passes: OptPasses,
+) -> (Vec<FunctionId>, Vec<PassReport>) {
+    let mut network = build_network(&obj_in, target);
+
+    let mut objects = Triggerlist { list: &mut obj_in };
+    let mut reports = Vec::new();
+    let mut size = network_size(&network, &objects, false);
+    let mut report = |name, network: &TriggerNetwork, objects: &Triggerlist, only_kept| {
+        let new_size = network_size(network, objects, only_kept);
+        reports.push(PassReport {
+            name,
+            triggers_removed: size.0 - new_size.0,
+            groups_removed: size.1 - new_size.1,
+        });
+        size = new_size;
+    };
+
+    // fix read write order
+    // not an optimization, more like a consistancy fix,
+    // so this runs even when every optimization is turned off
+    network = fix_read_write_order(&mut objects, &network, &mut closed_group, target);
+    report("read-write-order", &network, &objects, false);
+
+    if passes.instant_count {
+        simplify_instant_count_networks(&mut network, &mut objects, &mut closed_group, target);
+        report("instant-count", &network, &objects, false);
+    }
+
+    if passes.merge_triggers {
+        for (group, gang) in network.clone() {
+            if let Id::Specific(_) = group.id {
+                for (i, trigger) in gang.triggers.iter().enumerate() {
+                    if trigger.role != TriggerRole::Output {
+                        optimize_from(&mut network, &mut objects, (group, i), &mut closed_group);
+                    } else {
+                        network.get_mut(&group).unwrap().triggers[i].deleted = false;
+                    }
+                }
+            }
+        }
+        report("merge-triggers", &network, &objects, true);
+    } else {
+        // keep everything
+        for gang in network.values_mut() {
+            for trigger in &mut gang.triggers {
+                trigger.deleted = false;
+            }
+        }
+    }
+
+    // put into new fn ids and lists
+    let mut obj_out = rebuild(&network, &obj_in);
+
+    if passes.dedupe_groups {
+        // works on the finished triggers, so the groups merge-triggers
+        // left identical can be found
+        let mut network = build_network(&obj_out, target);
+        for gang in network.values_mut() {
+            for trigger in &mut gang.triggers {
+                trigger.deleted = false;
+            }
+        }
+        let mut objects = Triggerlist { list: &mut obj_out };
+        dedupe_groups(&mut network, &mut objects);
+        report("dedupe-groups", &network, &objects, true);
+        obj_out = rebuild(&network, &obj_out);
+    }
+
+    //profit
+
+    (obj_out, reports)
+}
+
+// triggers that keep listening after their group is activated,
+// so activating one copy of them twice is not the same as activating two copies once
+const ARMED_TRIGGERS: &[u16] = &[1595, 1611, 1812, 1815, 3604, 3615];
+
+// merges arbitrary groups that have exactly the same triggers, like the ones
+// made every time the same macro is called. runs until nothing changes,
+// since merging groups can make the groups that activate them the same too
+fn dedupe_groups(network: &mut TriggerNetwork, objects: &mut Triggerlist) {
+    loop {
+        // only groups that are nothing but the start of some triggers can be merged
+        let mut blocked = HashSet::new();
+        for (group, gang) in network.iter() {
+            for trigger in &gang.triggers {
+                if trigger.deleted {
+                    continue;
+                }
+                let obj = &objects[trigger.obj].0;
+                if let Some(ObjParam::Number(id)) = obj.params.get(&1) {
+                    if ARMED_TRIGGERS.contains(&(*id as u16)) {
+                        blocked.insert(*group);
+                    }
+                }
+                let activates = trigger.role != TriggerRole::Output;
+                for (key, param) in &obj.params {
+                    match param {
+                        ObjParam::Group(_) if *key == 57 || (*key == 51 && activates) => (),
+                        ObjParam::Group(g) => {
+                            blocked.insert(*g);
+                        }
+                        ObjParam::GroupList(list) => blocked.extend(list.iter().copied()),
+                        _ => (),
+                    }
+                }
+            }
+        }
+
+        // groups with the same triggers, in the same order
+        let mut same = BTreeMap::<Vec<String>, Vec<Group>>::new();
+        for (group, gang) in network.iter() {
+            if let Id::Specific(_) = group.id {
+                continue;
+            }
+            if blocked.contains(group) {
+                continue;
+            }
+            let mut triggers = gang
+                .triggers
+                .iter()
+                .filter(|t| !t.deleted)
+                .collect::<Vec<&Trigger>>();
+            if triggers.is_empty() {
+                continue;
+            }
+            triggers.sort_by_key(|t| t.order);
+            let signature = triggers
+                .iter()
+                .map(|t| {
+                    let mut params = objects[t.obj]
+                        .0
+                        .params
+                        .iter()
+                        .filter(|(k, _)| ![2, 3, 57].contains(*k))
+                        .collect::<Vec<(&u16, &ObjParam)>>();
+                    params.sort_by_key(|(k, _)| **k);
+                    format!("{:?}", params)
+                })
+                .collect::<Vec<String>>();
+            same.entry(signature).or_default().push(*group);
+        }
+
+        // keep the first group of each set, and point everything else to it
+        let mut replace = HashMap::new();
+        for groups in same.values() {
+            for g in &groups[1..] {
+                replace.insert(*g, groups[0]);
+            }
+        }
+        if replace.is_empty() {
+            break;
+        }
+        for (group, gang) in network.iter_mut() {
+            for trigger in &mut gang.triggers {
+                if replace.contains_key(group) {
+                    trigger.deleted = true;
+                } else if let Some(ObjParam::Group(g)) = objects[trigger.obj].0.params.get_mut(&51)
+                {
+                    if let Some(new) = replace.get(g) {
+                        *g = *new;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// sorts all triggers by their group, and counts the triggers activating each group
+pub(crate) fn build_network(obj_in: &[FunctionId], target: &Target) -> TriggerNetwork {
     let mut network = TriggerNetwork::new();
 
     // sort all triggers by their group
@@ -95,21 +361,21 @@ pub fn optimize(mut obj_in: Vec<FunctionId>, mut closed_group: u16) -> Vec<Funct
                 }
                 let trigger = Trigger {
                     obj: (f, o),
-                    role: get_role(*id as u16, hd),
+                    role: target.get_role(*id as u16, hd),
                     order: *order,
                     deleted: true,
                     optimized: false,
                 };
                 if let Some(ObjParam::Group(group)) = obj.params.get(&57) {
                     match network.get_mut(group) {
-                        Some(l) => (*l).triggers.push(trigger),
+                        Some(l) => l.triggers.push(trigger),
                         None => {
                             network.insert(*group, TriggerGang::new(vec![trigger]));
                         }
                     }
                 } else {
                     match network.get_mut(&NO_GROUP) {
-                        Some(l) => (*l).triggers.push(trigger),
+                        Some(l) => l.triggers.push(trigger),
                         None => {
                             network.insert(NO_GROUP, TriggerGang::new(vec![trigger]));
                         }
@@ -120,63 +386,239 @@ pub fn optimize(mut obj_in: Vec<FunctionId>, mut closed_group: u16) -> Vec<Funct
     }
 
     // count connection in for all triggers
-    for fnid in &obj_in {
+    for fnid in obj_in {
         for (obj, _) in &fnid.obj_list {
-            //if let Some(ObjParam::Number(id)) = obj.params.get(&1) {
-            //if get_role(*id as u16) != TriggerRole::Output {
-            if let Some(ObjParam::Group(id)) = obj.params.get(&51) {
-                if let Some(gang) = network.get_mut(id) {
-                    (*gang).connections_in += 1;
+            let group_keys = match obj.params.get(&1) {
+                Some(ObjParam::Number(id)) => match target.triggers.get(&(*id as u16)) {
+                    Some(info) => info.groups.clone(),
+                    None => vec![51],
+                },
+                _ => vec![51],
+            };
+            for key in group_keys {
+                if let Some(ObjParam::Group(id)) = obj.params.get(&key) {
+                    if let Some(gang) = network.get_mut(id) {
+                        gang.connections_in += 1;
+                    }
                 }
             }
-            //}
-            //}
         }
     }
 
-    //optimize
-    //optimize_network(&mut network);
+    network
+}
 
-    let mut objects = Triggerlist { list: &mut obj_in };
+// names of triggers for the trigger graph
+const TRIGGER_NAMES: &[(u16, &str)] = &[
+    (901, "move"),
+    (1006, "pulse"),
+    (1007, "alpha"),
+    (1049, "toggle"),
+    (1268, "spawn"),
+    (1346, "rotate"),
+    (1347, "follow"),
+    (1520, "shake"),
+    (1585, "animate"),
+    (1595, "touch"),
+    (1611, "count"),
+    (1616, "stop"),
+    (1811, "instant count"),
+    (1812, "on death"),
+    (1814, "follow player y"),
+    (1815, "collision"),
+    (1817, "pickup"),
+    (3604, "event"),
+    (3615, "time event"),
+];
+
+fn dot_group(group: &Group) -> String {
+    match group.id {
+        Id::Specific(0) => "no group".to_string(),
+        Id::Specific(n) => format!("{}g", n),
+        // arbitrary groups don't have their final id yet
+        Id::Arbitrary(n) => format!("?g{}", n),
+    }
+}
 
-    // fix read write order
-    // not an optimization, more like a consistancy fix
-    // also, like nothing works without this, so i should probably move
-    // this somewhere else if i want to add an option to not have optimization
-    network = fix_read_write_order(&mut objects, &network, &mut closed_group);
-
-    for (group, gang) in network.clone() {
-        if let Id::Specific(_) = group.id {
-            for (i, trigger) in gang.triggers.iter().enumerate() {
-                if trigger.role != TriggerRole::Output {
-                    optimize_from(&mut network, &mut objects, (group, i), &mut closed_group);
-                } else {
-                    (*network.get_mut(&group).unwrap()).triggers[i].deleted = false;
-                }
+fn dot_item(item: &Item) -> String {
+    match item.id {
+        Id::Specific(n) => format!("{}i", n),
+        Id::Arbitrary(n) => format!("?i{}", n),
+    }
+}
+
+// what a trigger does, like "spawn 0.5s" or "toggle off"
+fn dot_trigger_label(obj: &GdObj) -> String {
+    let id = match obj.params.get(&1) {
+        Some(ObjParam::Number(n)) => *n as u16,
+        _ => return "object".to_string(),
+    };
+    let mut label = match TRIGGER_NAMES.iter().find(|(i, _)| *i == id) {
+        Some((_, name)) => name.to_string(),
+        None => format!("trigger {}", id),
+    };
+    match id {
+        1268 => match obj.params.get(&63) {
+            Some(ObjParam::Number(n)) if *n > 0.0 => label += &format!(" {}s", n),
+            Some(ObjParam::Epsilon) => label += " epsilon",
+            _ => (),
+        },
+        1049 => {
+            let on = matches!(obj.params.get(&56), Some(ObjParam::Bool(true)));
+            label += if on { " on" } else { " off" };
+        }
+        1817 => {
+            if let Some(ObjParam::Item(item)) = obj.params.get(&80) {
+                label += &format!(
+                    " {} {}",
+                    dot_item(item),
+                    obj.params
+                        .get(&77)
+                        .map(|n| n.to_string())
+                        .unwrap_or_else(|| "0".to_string())
+                );
             }
         }
+        1611 | 1811 => {
+            if let Some(ObjParam::Item(item)) = obj.params.get(&80) {
+                let op = match obj.params.get(&88) {
+                    Some(ObjParam::Number(n)) if *n as i32 == 1 => ">",
+                    Some(ObjParam::Number(n)) if *n as i32 == 2 => "<",
+                    _ => "=",
+                };
+                label += &format!(
+                    " {} {} {}",
+                    dot_item(item),
+                    op,
+                    obj.params
+                        .get(&77)
+                        .map(|n| n.to_string())
+                        .unwrap_or_else(|| "0".to_string())
+                );
+            }
+        }
+        _ => (),
     }
+    label
+}
 
-    //for (g, len) in group_sizes {}
-
-    // put into new fn ids and lists
-
-    //profit
+// the trigger network as a graphviz graph, with groups as nodes
+// and an edge for every group a trigger activates or changes
+pub fn trigger_graph(obj_in: &[FunctionId], target: &Target) -> String {
+    let network = build_network(obj_in, target);
+    let objects = |ptr: ObjPtr| &obj_in[ptr.0].obj_list[ptr.1].0;
+
+    let mut nodes = BTreeMap::<Group, Vec<String>>::new();
+    let mut edges = Vec::new();
+    for (group, gang) in &network {
+        // triggers that don't target any group are listed on their node
+        let mut inside = Vec::new();
+        for trigger in &gang.triggers {
+            let obj = objects(trigger.obj);
+            let keys = match obj.params.get(&1) {
+                Some(ObjParam::Number(id)) => match target.triggers.get(&(*id as u16)) {
+                    Some(info) => info.groups.clone(),
+                    None => vec![51],
+                },
+                _ => vec![51],
+            };
+            let label = dot_trigger_label(obj);
+            let mut targets = false;
+            for key in keys {
+                if let Some(ObjParam::Group(to)) = obj.params.get(&key) {
+                    targets = true;
+                    nodes.entry(*to).or_default();
+                    edges.push((
+                        *group,
+                        *to,
+                        if key == 51 {
+                            label.clone()
+                        } else {
+                            format!("{} ({})", label, key)
+                        },
+                        trigger.role,
+                    ));
+                }
+            }
+            if !targets {
+                inside.push(label);
+            }
+        }
+        nodes.entry(*group).or_default().extend(inside);
+    }
 
-    rebuild(&network, &obj_in)
+    let mut out = String::from("digraph triggers {\n    node [shape=box];\n");
+    for (group, inside) in &nodes {
+        let mut label = dot_group(group);
+        for line in inside {
+            label += &format!("\\n{}", line);
+        }
+        // groups without triggers are objects that get moved, toggled, etc.
+        let shape = if network.contains_key(group) {
+            "box"
+        } else {
+            "ellipse"
+        };
+        out += &format!(
+            "    \"{}\" [label=\"{}\", shape={}];\n",
+            dot_group(group),
+            label,
+            shape
+        );
+    }
+    for (from, to, label, role) in edges {
+        let style = match role {
+            TriggerRole::Spawn => "solid",
+            TriggerRole::Func => "bold",
+            TriggerRole::Output => "dashed",
+        };
+        out += &format!(
+            "    \"{}\" -> \"{}\" [label=\"{}\", style={}];\n",
+            dot_group(&from),
+            dot_group(&to),
+            label,
+            style
+        );
+    }
+    out += "}\n";
+    out
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-enum IdData {
+pub enum IdData {
     //Group(Group),
     Block(Block),
     Item(Item),
 }
 
-fn reads_writes(t: Trigger, objects: &Triggerlist) -> (Vec<IdData>, Vec<IdData>) {
+pub fn reads_writes(
+    t: Trigger,
+    objects: &Triggerlist,
+    target: &Target,
+) -> (Vec<IdData>, Vec<IdData>) {
     let role = t.role;
     let obj = &objects[t.obj].0;
     let mut out = (Vec::new(), Vec::new());
+
+    let info = match obj.params.get(&1) {
+        Some(ObjParam::Number(id)) => target.triggers.get(&(*id as u16)),
+        _ => None,
+    };
+    if let Some(info) = info {
+        for (key, val) in &obj.params {
+            match val {
+                ObjParam::Item(i) if info.item_reads.contains(key) => out.0.push(IdData::Item(*i)),
+                ObjParam::Item(i) if info.item_writes.contains(key) => out.1.push(IdData::Item(*i)),
+                ObjParam::Block(b) if info.block_reads.contains(key) => {
+                    out.0.push(IdData::Block(*b))
+                }
+                _ => (),
+            }
+        }
+        return out;
+    }
+
+    // triggers that aren't in the table
     for (key, val) in &obj.params {
         let id_data = match val {
             //ObjParam::Group(g) => IDData::Group(*g),
@@ -212,7 +654,7 @@ fn get_targets<'a>(
             return None;
         }
     }
-    (*network.get_mut(&start.0).unwrap()).triggers[start.1].optimized = true;
+    network.get_mut(&start.0).unwrap().triggers[start.1].optimized = true;
     let trigger = network.get(&start.0).unwrap().triggers[start.1];
     let start_obj = &objects[trigger.obj].0.params;
 
@@ -222,7 +664,7 @@ fn get_targets<'a>(
 
     if let Some(ObjParam::Group(g)) = start_obj.get(&51) {
         if let Id::Specific(_) = g.id {
-            (*network.get_mut(&start.0).unwrap()).triggers[start.1].deleted = false;
+            network.get_mut(&start.0).unwrap().triggers[start.1].deleted = false;
             return None;
         }
 
@@ -248,17 +690,11 @@ fn get_targets<'a>(
 
     let added_delay = match start_obj.get(&63) {
         Some(ObjParam::Number(n)) => (*n * 1000.0) as u32,
-        Some(ObjParam::Epsilon) => {
-            if delay == 0 {
-                50
-            } else {
-                0
-            }
-        }
+        Some(ObjParam::Epsilon) if delay == 0 => 50,
         _ => 0,
     };
 
-    let mut out = HashSet::<(Group, u32)>::new();
+    let mut out = BTreeSet::<(Group, u32)>::new();
 
     for (i, g) in list {
         let trigger_ptr = (g, i);
@@ -274,23 +710,23 @@ fn get_targets<'a>(
                 out.insert(target_out);
             }
         } else if network[&trigger_ptr.0].connections_in > 1 {
-            (*network.get_mut(&trigger_ptr.0).unwrap()).triggers[trigger_ptr.1].deleted = false;
+            network.get_mut(&trigger_ptr.0).unwrap().triggers[trigger_ptr.1].deleted = false;
             if optimize_from(network, objects, trigger_ptr, closed_group) {
                 out.insert(target_out);
             } else {
-                (*network.get_mut(&trigger_ptr.0).unwrap()).triggers[trigger_ptr.1].deleted = true;
+                network.get_mut(&trigger_ptr.0).unwrap().triggers[trigger_ptr.1].deleted = true;
             }
         } else {
             match trigger.role {
                 TriggerRole::Output => {
-                    (*network.get_mut(&trigger_ptr.0).unwrap()).triggers[trigger_ptr.1].deleted =
+                    network.get_mut(&trigger_ptr.0).unwrap().triggers[trigger_ptr.1].deleted =
                         false;
                     out.insert(target_out);
                 }
                 TriggerRole::Func => {
                     if optimize_from(network, objects, trigger_ptr, closed_group) {
-                        (*network.get_mut(&trigger_ptr.0).unwrap()).triggers[trigger_ptr.1]
-                            .deleted = false;
+                        network.get_mut(&trigger_ptr.0).unwrap().triggers[trigger_ptr.1].deleted =
+                            false;
                         out.insert(target_out);
                     }
                 }
@@ -305,7 +741,7 @@ fn get_targets<'a>(
                     ) {
                         Some(children) => out.extend(children),
                         None => {
-                            (*network.get_mut(&trigger_ptr.0).unwrap()).triggers[trigger_ptr.1]
+                            network.get_mut(&trigger_ptr.0).unwrap().triggers[trigger_ptr.1]
                                 .deleted = false;
                             out.insert(target_out);
                         }
@@ -315,12 +751,12 @@ fn get_targets<'a>(
         }
     }
 
-    (*network.get_mut(&start.0).unwrap()).triggers[start.1].deleted = true;
+    network.get_mut(&start.0).unwrap().triggers[start.1].deleted = true;
 
     Some(out.iter().copied().collect())
 }
 
-fn create_spawn_trigger(
+pub fn create_spawn_trigger(
     trigger: Trigger,
     target_group: Group,
     group: Option<Group>,
@@ -346,6 +782,7 @@ fn create_spawn_trigger(
         unique_id: objects[trigger.obj].0.unique_id,
         sync_group: 0,
         sync_part: 0,
+        source: objects[trigger.obj].0.source.clone(),
     };
 
     (*objects.list)[trigger.obj.0]
@@ -366,14 +803,14 @@ fn create_spawn_trigger(
 
     if let Some(ObjParam::Group(group)) = new_obj.params.get(&57) {
         match network.get_mut(group) {
-            Some(gang) => (*gang).triggers.push(new_trigger),
+            Some(gang) => gang.triggers.push(new_trigger),
             None => {
                 network.insert(*group, TriggerGang::new(vec![new_trigger]));
             }
         }
     } else {
         match network.get_mut(&NO_GROUP) {
-            Some(gang) => (*gang).triggers.push(new_trigger),
+            Some(gang) => gang.triggers.push(new_trigger),
             None => {
                 network.insert(NO_GROUP, TriggerGang::new(vec![new_trigger]));
             }
@@ -381,8 +818,8 @@ fn create_spawn_trigger(
     }
 }
 
-fn optimize_from<'a>(
-    network: &'a mut TriggerNetwork,
+fn optimize_from(
+    network: &mut TriggerNetwork,
     objects: &mut Triggerlist,
     start: (Group, usize),
     closed_group: &mut u16,
@@ -391,7 +828,7 @@ fn optimize_from<'a>(
 
     let trigger = network[&start.0].triggers[start.1];
     if trigger.role == TriggerRole::Output {
-        (*network.get_mut(&start.0).unwrap()).triggers[start.1].deleted = false;
+        network.get_mut(&start.0).unwrap().triggers[start.1].deleted = false;
         return true;
     }
 
@@ -429,8 +866,8 @@ fn optimize_from<'a>(
                 .0
                 .params
                 .insert(51, ObjParam::Group(targets[0].0));
-            (*network.get_mut(&start.0).unwrap()).triggers[start.1].deleted = false;
-            (*network.get_mut(&start.0).unwrap()).triggers[start.1].optimized = true;
+            network.get_mut(&start.0).unwrap().triggers[start.1].deleted = false;
+            network.get_mut(&start.0).unwrap().triggers[start.1].optimized = true;
             return true;
         }
 
@@ -445,7 +882,7 @@ fn optimize_from<'a>(
                 .params
                 .insert(51, ObjParam::Group(new_group));
 
-            (*network.get_mut(&start.0).unwrap()).triggers[start.1].deleted = false;
+            network.get_mut(&start.0).unwrap().triggers[start.1].deleted = false;
 
             Some(new_group)
         } else {
@@ -482,8 +919,8 @@ fn optimize_from<'a>(
                     }
                 }
 
-                for trigger in &mut (*network.get_mut(&g).unwrap()).triggers {
-                    (*trigger).optimized = true;
+                for trigger in &mut network.get_mut(&g).unwrap().triggers {
+                    trigger.optimized = true;
                 }
 
             //continue;
@@ -502,7 +939,7 @@ fn optimize_from<'a>(
 
         true
     } else {
-        (*network.get_mut(&start.0).unwrap()).triggers[start.1].deleted = false;
+        network.get_mut(&start.0).unwrap().triggers[start.1].deleted = false;
         true
     }
 }
@@ -510,7 +947,7 @@ fn optimize_from<'a>(
 fn rebuild(network: &TriggerNetwork, orig_structure: &[FunctionId]) -> Vec<FunctionId> {
     let mut out = orig_structure.to_vec();
     for el in &mut out {
-        (*el).obj_list.clear();
+        el.obj_list.clear();
     }
 
     for gang in network.values() {
@@ -540,6 +977,7 @@ fn fix_read_write_order(
     objects: &mut Triggerlist,
     network: &TriggerNetwork,
     closed_group: &mut u16,
+    target: &Target,
 ) -> TriggerNetwork {
     let mut new_network = TriggerNetwork::new();
     for (group, gang) in network {
@@ -560,7 +998,7 @@ fn fix_read_write_order(
         //let mut previous_delays = Vec::new();
 
         for trigger in &gang.triggers {
-            let (reads, writes) = reads_writes(*trigger, objects);
+            let (reads, writes) = reads_writes(*trigger, objects, target);
 
             if reads.iter().any(|x| written_to.contains(x))
                 || writes.iter().any(|x| read_from.contains(x))
@@ -657,7 +1095,9 @@ fn fix_read_write_order(
             //         .push(*trigger),
             // };
 
-            (*new_network.get_mut(&current_group).unwrap())
+            new_network
+                .get_mut(&current_group)
+                .unwrap()
                 .triggers
                 .push(*trigger);
 
@@ -672,3 +1112,430 @@ fn fix_read_write_order(
     }
     new_network
 }
+
+// triggers and keys that only change where objects are or how they look.
+// spawn triggered triggers don't care about either, since they run wherever they are
+const VISUAL_REFS: &[(u16, u16)] = &[
+    (901, 51),
+    (1006, 51),
+    (1007, 51),
+    (1346, 51),
+    (1347, 51),
+    (1585, 51),
+    (1814, 51),
+];
+
+// what is in an arbitrary group, and what the objects referring to it do with it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+struct GroupUse {
+    // spawn triggered triggers
+    spawned: bool,
+    // triggers that run when the player gets to them
+    placed: bool,
+    // spawn triggers activating it
+    spawned_by: bool,
+    // triggers moving it or changing how it looks
+    moved_by: bool,
+    // toggle and stop triggers, and everything else
+    changed_by: bool,
+}
+
+impl GroupUse {
+    // whether the objects referring to this group would do something to the triggers in `other`
+    fn affects(&self, other: &GroupUse) -> bool {
+        (self.spawned_by && other.spawned)
+            || (self.moved_by && other.placed)
+            || (self.changed_by && (other.spawned || other.placed))
+    }
+
+    fn interferes(&self, other: &GroupUse) -> bool {
+        self.affects(other) || other.affects(self)
+    }
+
+    fn add(&mut self, other: &GroupUse) {
+        self.spawned |= other.spawned;
+        self.placed |= other.placed;
+        self.spawned_by |= other.spawned_by;
+        self.moved_by |= other.moved_by;
+        self.changed_by |= other.changed_by;
+    }
+}
+
+fn arbitrary_groups(param: &ObjParam) -> Vec<ArbitraryId> {
+    let groups = match param {
+        ObjParam::Group(g) => vec![*g],
+        ObjParam::GroupList(l) => l.clone(),
+        _ => return Vec::new(),
+    };
+    groups
+        .iter()
+        .filter_map(|g| match g.id {
+            Id::Arbitrary(id) => Some(id),
+            _ => None,
+        })
+        .collect()
+}
+
+// gives arbitrary groups the same id when nothing can tell them apart, by colouring
+// the graph of groups that interfere with each other.
+// two trigger chains can't share a group just because they never run at the same time:
+// activating a group runs every spawn triggered trigger in it, whenever that happens.
+// so groups interfere when the triggers referring to one of them would do something
+// to the triggers in the other. groups with other objects in them are never changed.
+// returns the groups that were replaced, and the group that replaced them
+pub fn recycle_groups(objects: &mut [GdObj], target: &Target) -> Vec<(ArbitraryId, ArbitraryId)> {
+    let mut uses = BTreeMap::<ArbitraryId, GroupUse>::new();
+    let mut fixed = HashSet::<ArbitraryId>::new();
+    for obj in objects.iter() {
+        let obj_id = match obj.params.get(&1) {
+            Some(ObjParam::Number(id)) if obj.mode == ObjectMode::Trigger => Some(*id as u16),
+            _ => None,
+        };
+        for (key, param) in &obj.params {
+            for g in arbitrary_groups(param) {
+                let group_use = uses.entry(g).or_default();
+                if *key == 57 {
+                    match obj.mode {
+                        ObjectMode::Object => {
+                            fixed.insert(g);
+                        }
+                        ObjectMode::Trigger => {
+                            if let Some(ObjParam::Bool(true)) = obj.params.get(&62) {
+                                group_use.spawned = true
+                            } else {
+                                group_use.placed = true
+                            }
+                        }
+                    }
+                    continue;
+                }
+                match obj_id {
+                    Some(id)
+                        if matches!(
+                            target.triggers.get(&id),
+                            Some(info) if info.role == TriggerRole::Spawn && info.groups.contains(key)
+                        ) =>
+                    {
+                        group_use.spawned_by = true
+                    }
+                    Some(id) if VISUAL_REFS.contains(&(id, *key)) => group_use.moved_by = true,
+                    _ => group_use.changed_by = true,
+                }
+            }
+        }
+    }
+    for g in &fixed {
+        uses.remove(g);
+    }
+
+    // groups that use their group the same way all interfere with the same groups,
+    // so the number of groups each one interferes with can be counted by use
+    let mut counts = HashMap::<GroupUse, usize>::new();
+    for group_use in uses.values() {
+        *counts.entry(*group_use).or_default() += 1;
+    }
+    let degree = |group_use: &GroupUse| {
+        counts
+            .iter()
+            .filter(|(other, _)| group_use.interferes(other))
+            .map(|(_, count)| count)
+            .sum::<usize>()
+    };
+
+    // greedy colouring, starting with the groups that interfere with the most others
+    let mut order = uses
+        .iter()
+        .map(|(g, group_use)| (*g, *group_use, degree(group_use)))
+        .collect::<Vec<(ArbitraryId, GroupUse, usize)>>();
+    order.sort_by_key(|(g, _, degree)| (std::cmp::Reverse(*degree), *g));
+
+    // the groups with each colour, and how they are used together
+    let mut colours = Vec::<(Vec<ArbitraryId>, GroupUse)>::new();
+    for (g, group_use, _) in order {
+        match colours
+            .iter_mut()
+            .find(|(_, colour_use)| !colour_use.interferes(&group_use))
+        {
+            Some((groups, colour_use)) => {
+                groups.push(g);
+                colour_use.add(&group_use);
+            }
+            None => colours.push((vec![g], group_use)),
+        }
+    }
+
+    let mut replace = HashMap::new();
+    for (groups, _) in &colours {
+        let first = *groups.iter().min().unwrap();
+        for g in groups {
+            if *g != first {
+                replace.insert(*g, first);
+            }
+        }
+    }
+    if replace.is_empty() {
+        return Vec::new();
+    }
+
+    for obj in objects.iter_mut() {
+        for param in obj.params.values_mut() {
+            let groups = match param {
+                ObjParam::Group(g) => vec![g],
+                ObjParam::GroupList(l) => l.iter_mut().collect(),
+                _ => continue,
+            };
+            for g in groups {
+                if let Id::Arbitrary(id) = g.id {
+                    if let Some(new) = replace.get(&id) {
+                        g.id = Id::Arbitrary(*new);
+                    }
+                }
+            }
+        }
+        // an object can end up in the same group twice
+        if let Some(ObjParam::GroupList(l)) = obj.params.get_mut(&57) {
+            let mut seen = HashSet::new();
+            l.retain(|g| seen.insert(*g));
+        }
+    }
+
+    let mut out = replace
+        .into_iter()
+        .collect::<Vec<(ArbitraryId, ArbitraryId)>>();
+    out.sort_unstable();
+    out
+}
+
+// gives the replaced groups the id of the group that replaced them, for the lockfile and the id map
+pub fn map_recycled_groups(id_maps: &mut IdMaps, recycled: &[(ArbitraryId, ArbitraryId)]) {
+    for (from, to) in recycled {
+        if let Some(id) = id_maps[0].get(to).copied() {
+            id_maps[0].insert(*from, id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{function, group, item, obj, trigger};
+
+    // a trigger activating `target`, in `in_group` if there is one. the order
+    // is also its unique id, like every object the compiler makes has its own
+    fn ordered(obj_id: u16, target: u16, in_group: Option<u16>, order: usize) -> (GdObj, usize) {
+        let mut params = vec![
+            (1, ObjParam::Number(obj_id.into())),
+            (51, group(Id::Arbitrary(target))),
+        ];
+        if let Some(g) = in_group {
+            params.push((57, group(Id::Arbitrary(g))));
+        }
+        let (mut obj, _) = trigger(params);
+        obj.unique_id = order;
+        (obj, order)
+    }
+
+    fn obj_ids(func_ids: &[FunctionId]) -> Vec<u16> {
+        let mut ids = func_ids
+            .iter()
+            .flat_map(|f| f.obj_list.iter())
+            .map(|(o, _)| match o.params.get(&1) {
+                Some(ObjParam::Number(n)) => *n as u16,
+                _ => 0,
+            })
+            .collect::<Vec<u16>>();
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn levels() {
+        // a chain of spawn triggers ending in a move trigger
+        let chain = vec![function(vec![
+            ordered(1268, 1, None, 0),
+            ordered(1268, 2, Some(1), 1),
+            ordered(901, 3, Some(2), 2),
+        ])];
+        let target = Target::default();
+
+        let (out, reports) = optimize(
+            chain.clone(),
+            10,
+            &target,
+            OptPasses::from_level(0).unwrap(),
+        );
+        assert_eq!(obj_ids(&out), vec![901, 1268, 1268]);
+        assert_eq!(
+            reports.iter().map(|r| r.name).collect::<Vec<&str>>(),
+            vec!["read-write-order"]
+        );
+
+        // the move trigger is activated right away
+        let (out, reports) = optimize(chain, 10, &target, OptPasses::from_level(1).unwrap());
+        assert_eq!(obj_ids(&out), vec![901]);
+        assert_eq!(reports[1].name, "merge-triggers");
+        assert_eq!(reports[1].triggers_removed, 2);
+    }
+
+    #[test]
+    fn dedupe() {
+        let mut passes = OptPasses::from_level(0).unwrap();
+        passes.toggle("+dedupe-groups").unwrap();
+        let target = Target::default();
+
+        // ?g1 and ?g2 do the same thing, so one of them can go
+        let (out, reports) = optimize(
+            vec![function(vec![
+                ordered(1268, 1, None, 0),
+                ordered(1268, 2, None, 1),
+                ordered(901, 5, Some(1), 2),
+                ordered(901, 5, Some(2), 3),
+            ])],
+            10,
+            &target,
+            passes,
+        );
+        assert_eq!(obj_ids(&out), vec![901, 1268, 1268]);
+        assert!(out
+            .iter()
+            .flat_map(|f| f.obj_list.iter())
+            .filter(|(o, _)| o.params.get(&1) == Some(&ObjParam::Number(1268.0)))
+            .all(|(o, _)| o.params.get(&51) == Some(&group(Id::Arbitrary(1)))));
+        assert_eq!(reports.last().unwrap().name, "dedupe-groups");
+        assert_eq!(reports.last().unwrap().groups_removed, 1);
+
+        // triggers that keep listening can't be shared, since each copy counts on its own
+        for armed in ARMED_TRIGGERS {
+            let (out, _) = optimize(
+                vec![function(vec![
+                    ordered(1268, 1, None, 0),
+                    ordered(1268, 2, None, 1),
+                    ordered(*armed, 5, Some(1), 2),
+                    ordered(*armed, 5, Some(2), 3),
+                ])],
+                10,
+                &target,
+                passes,
+            );
+            assert_eq!(obj_ids(&out).len(), 4, "{}", armed);
+        }
+    }
+
+    #[test]
+    fn pass_toggles() {
+        let mut passes = OptPasses::from_level(1).unwrap();
+        passes.toggle("+instant-count").unwrap();
+        assert!(passes.instant_count);
+        passes.toggle("-merge-triggers").unwrap();
+        assert!(!passes.merge_triggers);
+        assert!(passes.toggle("-read-write-order").is_err());
+        assert!(passes.toggle("instant-count").is_err());
+        assert!(passes.toggle("+unknown").is_err());
+        assert!(OptPasses::from_level(3).is_err());
+
+        assert!(OptPasses::default().recycle_groups);
+        assert!(!OptPasses::from_level(1).unwrap().recycle_groups);
+    }
+
+    #[test]
+    fn trigger_graphs() {
+        let func_ids = vec![function(vec![
+            trigger(vec![
+                (1, ObjParam::Number(1268.0)),
+                (51, group(Id::Arbitrary(1))),
+                (63, ObjParam::Number(0.5)),
+            ]),
+            trigger(vec![
+                (1, ObjParam::Number(1049.0)),
+                (51, group(Id::Specific(5))),
+                (57, group(Id::Arbitrary(1))),
+            ]),
+            trigger(vec![
+                (1, ObjParam::Number(1817.0)),
+                (57, group(Id::Arbitrary(1))),
+                (77, ObjParam::Number(2.0)),
+                (80, item(Id::Specific(3))),
+            ]),
+        ])];
+        // the pickup trigger doesn't target a group, so it's listed on its own group
+        assert_eq!(
+            trigger_graph(&func_ids, &Target::default()),
+            concat!(
+                "digraph triggers {\n",
+                "    node [shape=box];\n",
+                "    \"no group\" [label=\"no group\", shape=box];\n",
+                "    \"5g\" [label=\"5g\", shape=ellipse];\n",
+                "    \"?g1\" [label=\"?g1\\npickup 3i 2\", shape=box];\n",
+                "    \"no group\" -> \"?g1\" [label=\"spawn 0.5s\", style=solid];\n",
+                "    \"?g1\" -> \"5g\" [label=\"toggle off\", style=dashed];\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn recycled_groups() {
+        let arbitrary = |id| group(Id::Arbitrary(id));
+        let spawned = |obj_id: f64, target: u16, in_group: u16| {
+            obj(
+                ObjectMode::Trigger,
+                vec![
+                    (1, ObjParam::Number(obj_id)),
+                    (51, arbitrary(target)),
+                    (57, arbitrary(in_group)),
+                    (62, ObjParam::Bool(true)),
+                ],
+            )
+        };
+        let mut objects = vec![
+            // spawns ?g1 and ?g5 at the start of the level
+            obj(
+                ObjectMode::Trigger,
+                vec![(1, ObjParam::Number(1268.0)), (51, arbitrary(1))],
+            ),
+            obj(
+                ObjectMode::Trigger,
+                vec![(1, ObjParam::Number(1268.0)), (51, arbitrary(5))],
+            ),
+            // ?g2 has an object in it, so it keeps its own id
+            spawned(901.0, 2, 1),
+            obj(ObjectMode::Object, vec![(57, arbitrary(2))]),
+            // ?g3 and ?g7 are never activated
+            spawned(1049.0, 4, 3),
+            spawned(1268.0, 1, 7),
+            // moving ?g6 does nothing to spawn triggered triggers
+            spawned(901.0, 6, 5),
+        ];
+        let recycled = recycle_groups(&mut objects, &Target::default());
+
+        // spawning ?g1 would run the triggers in ?g5 if they shared an id (and the
+        // other way around), and spawning either would run the ones in ?g3 and ?g7.
+        // toggling ?g4 would turn off the triggers in ?g1, ?g3 and ?g5
+        assert_eq!(recycled, vec![(6, 1), (7, 3)]);
+        assert_eq!(objects[6].params[&51], arbitrary(1));
+        assert_eq!(objects[5].params[&57], arbitrary(3));
+        assert_eq!(objects[3].params[&57], arbitrary(2));
+
+        let mut id_maps = IdMaps::default();
+        id_maps[0].insert(1, 10);
+        id_maps[0].insert(3, 11);
+        map_recycled_groups(&mut id_maps, &recycled);
+        assert_eq!(id_maps[0][&6], 10);
+        assert_eq!(id_maps[0][&7], 11);
+
+        // moving a trigger that runs when the player gets to it changes when it runs,
+        // so ?g3 can't share with ?g1. ?g2 is never activated, so it can
+        let mut objects = vec![
+            obj(
+                ObjectMode::Trigger,
+                vec![(1, ObjParam::Number(1817.0)), (57, arbitrary(1))],
+            ),
+            spawned(901.0, 1, 2),
+            spawned(901.0, 3, 2),
+        ];
+        assert_eq!(
+            recycle_groups(&mut objects, &Target::default()),
+            vec![(2, 1)]
+        );
+    }
+}
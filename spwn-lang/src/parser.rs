@@ -34,11 +34,12 @@ macro_rules! expected {
             ),
             pos: $tokens.position(),
             file: $notes.file.clone(),
-        });
+        })
     };
 }
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum SyntaxError {
     ExpectedErr {
         expected: String,
@@ -583,24 +584,19 @@ pub fn parse_spwn(
 
     let start_tag = check_for_tag(&mut tokens, &mut notes)?;
     notes.tag = start_tag;
-    loop {
-        //+ do something if we have tokens. if no more tokens, leave loop
-        match tokens.next(false) {
-            //oops we just advanced the tokens in an attempt to check if we have any
-            Some(_) => {
-                tokens.previous_no_ignore(false); //bring tokens back to original
-
-                //+ we are going to parse the tokens
-                let parsed = parse_statement(&mut tokens, &mut notes)?;
-                // if parsed.comment.0 == None && !statements.is_empty() {
-                //     parsed.comment.0 = statements.last().unwrap().comment.1.clone();
-                //     (*statements.last_mut().unwrap()).comment.1 = None;
-                // }
+    //+ do something if we have tokens. if no more tokens (probably end of file), leave loop
+    //oops we just advanced the tokens in an attempt to check if we have any
+    while tokens.next(false).is_some() {
+        tokens.previous_no_ignore(false); //bring tokens back to original
+
+        //+ we are going to parse the tokens
+        let parsed = parse_statement(&mut tokens, &mut notes)?;
+        // if parsed.comment.0 == None && !statements.is_empty() {
+        //     parsed.comment.0 = statements.last().unwrap().comment.1.clone();
+        //     (*statements.last_mut().unwrap()).comment.1 = None;
+        // }
 
-                statements.push(parsed)
-            }
-            None => break, //+ no more tokens, probably end of file
-        }
+        statements.push(parsed);
 
         //+ can't find any more tokens that are valid syntax, checking for line separator
         match tokens.next(true) {
@@ -1248,7 +1244,7 @@ fn parse_expr(
 
             // iterate though the operators until we get one like =
             while !old_operators.is_empty() {
-                if operator_precedence(&old_operators.last().unwrap()) == 0 {
+                if operator_precedence(old_operators.last().unwrap()) == 0 {
                     break;
                 }
 
@@ -2037,10 +2033,7 @@ fn parse_variable(
                         }
                     }
 
-                    Err(_) => match parse_macro_def(tokens, notes) {
-                        Ok(mac) => mac,
-                        Err(e) => return Err(e),
-                    },
+                    Err(_) => parse_macro_def(tokens, notes)?,
                 }
             } else {
                 let expr = parse_expr(tokens, notes, true, true)?;
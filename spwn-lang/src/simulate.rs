@@ -0,0 +1,896 @@
+// runs the triggers of a level without geometry dash, for testing what a level does
+use crate::ast::ObjectMode;
+use crate::builtin::{Block, Group, Id, Item};
+use crate::levelstring::{GdObj, ObjParam};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// how fast the player moves at normal speed, in units per second.
+// triggers that aren't spawn or touch triggered run when the player passes them
+const PLAYER_SPEED: f64 = 311.58;
+// how many triggers can activate each other at the same time before it counts as a loop
+const MAX_DEPTH: usize = 1000;
+const MAX_STEPS: usize = 1_000_000;
+
+// something the player does, at a certain time
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    Touch,
+    Collision(Block, Block),
+    CollisionEnd(Block, Block),
+    Death,
+    // activates a group, like a spawn trigger would
+    Spawn(Group),
+}
+
+// something that happens in the level
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    // a trigger that changes something in the level (move, pulse, color, etc.) ran
+    Output(usize),
+    // the item, its new value and the trigger that changed it
+    ItemChanged(Item, i32, usize),
+    Toggled(Group, bool, usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Pending {
+    // a group activated by a spawn trigger (the object), or by the input timeline
+    Activate(Group, Option<usize>),
+    // a trigger that the player passes
+    Run(usize),
+    Input(Input),
+}
+
+pub struct Simulation {
+    objects: Vec<GdObj>,
+    by_group: HashMap<Group, Vec<usize>>,
+    // (time in milliseconds, order it was added in)
+    queue: BTreeMap<(u64, usize), Pending>,
+    added: usize,
+    steps: usize,
+    // in milliseconds
+    pub time: u64,
+    items: HashMap<Item, i32>,
+    toggled_off: HashSet<Group>,
+    // count, collision, touch and death triggers that are waiting for something to happen
+    armed: Vec<usize>,
+    // everything that happened, with the time in milliseconds
+    pub log: Vec<(u64, Effect)>,
+}
+
+fn obj_id(obj: &GdObj) -> u16 {
+    match obj.params.get(&1) {
+        Some(ObjParam::Number(n)) => *n as u16,
+        _ => 0,
+    }
+}
+
+fn obj_groups(obj: &GdObj) -> Vec<Group> {
+    match obj.params.get(&57) {
+        Some(ObjParam::Group(g)) => vec![*g],
+        Some(ObjParam::GroupList(l)) => l.clone(),
+        _ => Vec::new(),
+    }
+}
+
+fn get_bool(obj: &GdObj, key: u16) -> bool {
+    obj.params.get(&key) == Some(&ObjParam::Bool(true))
+}
+
+fn get_number(obj: &GdObj, key: u16) -> f64 {
+    match obj.params.get(&key) {
+        Some(ObjParam::Number(n)) => *n,
+        _ => 0.0,
+    }
+}
+
+fn get_group(obj: &GdObj, key: u16) -> Option<Group> {
+    match obj.params.get(&key) {
+        Some(ObjParam::Group(g)) => Some(*g),
+        _ => None,
+    }
+}
+
+// delays are kept in milliseconds, like in the optimizer
+fn delay_millis(obj: &GdObj) -> u64 {
+    match obj.params.get(&63) {
+        Some(ObjParam::Number(n)) if *n > 0.0 => (*n * 1000.0).round() as u64,
+        // the smallest delay that is reliable
+        Some(ObjParam::Epsilon) => 50,
+        _ => 0,
+    }
+}
+
+impl Simulation {
+    pub fn new(objects: Vec<GdObj>, inputs: &[(u64, Input)]) -> Self {
+        let mut by_group = HashMap::<Group, Vec<usize>>::new();
+        for (i, obj) in objects.iter().enumerate() {
+            for g in obj_groups(obj) {
+                by_group.entry(g).or_default().push(i);
+            }
+        }
+
+        let mut sim = Simulation {
+            objects,
+            by_group,
+            queue: BTreeMap::new(),
+            added: 0,
+            steps: 0,
+            time: 0,
+            items: HashMap::new(),
+            toggled_off: HashSet::new(),
+            armed: Vec::new(),
+            log: Vec::new(),
+        };
+
+        // triggers the player runs into
+        for i in 0..sim.objects.len() {
+            let obj = &sim.objects[i];
+            if obj.mode == ObjectMode::Trigger && !get_bool(obj, 62) && !get_bool(obj, 11) {
+                let time = (get_number(obj, 2).max(0.0) / PLAYER_SPEED * 1000.0).round() as u64;
+                sim.schedule(time, Pending::Run(i));
+            }
+        }
+        for (time, input) in inputs {
+            sim.schedule(*time, Pending::Input(input.clone()));
+        }
+        sim
+    }
+
+    fn schedule(&mut self, time: u64, pending: Pending) {
+        self.queue.insert((time, self.added), pending);
+        self.added += 1;
+    }
+
+    pub fn item(&self, item: Item) -> i32 {
+        *self.items.get(&item).unwrap_or(&0)
+    }
+
+    pub fn is_on(&self, group: Group) -> bool {
+        !self.toggled_off.contains(&group)
+    }
+
+    pub fn objects(&self) -> &[GdObj] {
+        &self.objects
+    }
+
+    // true when nothing is left to happen
+    pub fn finished(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    // runs everything that happens up to (and at) the given time
+    pub fn run_until(&mut self, time: u64) -> Result<(), String> {
+        loop {
+            let key = match self.queue.keys().next() {
+                Some(k) if k.0 <= time => *k,
+                _ => break,
+            };
+            let pending = self.queue.remove(&key).unwrap();
+            self.time = key.0;
+            self.steps += 1;
+            if self.steps > MAX_STEPS {
+                return Err(format!(
+                    "The simulation ran more than {} steps (stopped at {}ms)",
+                    MAX_STEPS, self.time
+                ));
+            }
+            match pending {
+                Pending::Activate(group, _) => self.activate_group(group, 0)?,
+                Pending::Run(i) => {
+                    if self.enabled(i) {
+                        self.run_trigger(i, 0)?
+                    }
+                }
+                Pending::Input(input) => self.input(input)?,
+            }
+        }
+        self.time = self.time.max(time);
+        Ok(())
+    }
+
+    fn enabled(&self, i: usize) -> bool {
+        !obj_groups(&self.objects[i])
+            .iter()
+            .any(|g| self.toggled_off.contains(g))
+    }
+
+    fn toggle(&mut self, group: Group, on: bool, trigger: usize) {
+        if on {
+            self.toggled_off.remove(&group);
+        } else {
+            self.toggled_off.insert(group);
+        }
+        self.log
+            .push((self.time, Effect::Toggled(group, on, trigger)));
+    }
+
+    fn activate_group(&mut self, group: Group, depth: usize) -> Result<(), String> {
+        if depth > MAX_DEPTH {
+            return Err(format!(
+                "Triggers keep activating each other without a delay (at {}ms)",
+                self.time
+            ));
+        }
+        let list = match self.by_group.get(&group) {
+            Some(l) => l.clone(),
+            None => return Ok(()),
+        };
+        for i in list {
+            let obj = &self.objects[i];
+            if obj.mode == ObjectMode::Trigger && get_bool(obj, 62) && self.enabled(i) {
+                self.run_trigger(i, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    // turns a group on, which also activates the spawn triggered triggers in it
+    fn toggle_on(&mut self, group: Group, trigger: usize, depth: usize) -> Result<(), String> {
+        self.toggle(group, true, trigger);
+        self.activate_group(group, depth)
+    }
+
+    // activates or toggles off the target group, depending on the activate group option
+    // (or the toggle mode, for touch triggers)
+    fn activate_target(&mut self, i: usize, depth: usize) -> Result<(), String> {
+        let obj = &self.objects[i];
+        if let Some(target) = get_group(obj, 51) {
+            if obj_id(obj) == 1595 {
+                match get_number(obj, 82) as u8 {
+                    1 => self.toggle_on(target, i, depth)?,
+                    2 => self.toggle(target, false, i),
+                    _ => {
+                        if self.is_on(target) {
+                            self.toggle(target, false, i)
+                        } else {
+                            self.toggle_on(target, i, depth)?
+                        }
+                    }
+                }
+            } else if get_bool(obj, 56) {
+                self.activate_group(target, depth)?;
+            } else {
+                self.toggle(target, false, i);
+            }
+        }
+        Ok(())
+    }
+
+    fn set_item(
+        &mut self,
+        item: Item,
+        value: i32,
+        trigger: usize,
+        depth: usize,
+    ) -> Result<(), String> {
+        self.items.insert(item, value);
+        self.log
+            .push((self.time, Effect::ItemChanged(item, value, trigger)));
+
+        // count triggers
+        for i in self.armed.clone() {
+            let obj = &self.objects[i];
+            if obj_id(obj) == 1611
+                && obj.params.get(&80) == Some(&ObjParam::Item(item))
+                && get_number(obj, 77).round() as i32 == value
+                && self.enabled(i)
+            {
+                if !get_bool(obj, 104) {
+                    self.armed.retain(|a| *a != i);
+                }
+                self.activate_target(i, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn run_trigger(&mut self, i: usize, depth: usize) -> Result<(), String> {
+        let obj = &self.objects[i];
+        match obj_id(obj) {
+            // spawn
+            1268 => {
+                if let Some(target) = get_group(obj, 51) {
+                    let delay = delay_millis(obj);
+                    if delay == 0 {
+                        self.activate_group(target, depth)?;
+                    } else {
+                        self.schedule(self.time + delay, Pending::Activate(target, Some(i)));
+                    }
+                }
+            }
+            // toggle
+            1049 => {
+                if let Some(target) = get_group(obj, 51) {
+                    if get_bool(obj, 56) {
+                        self.toggle_on(target, i, depth)?;
+                    } else {
+                        self.toggle(target, false, i);
+                    }
+                }
+            }
+            // pickup
+            1817 => {
+                if let Some(ObjParam::Item(item)) = obj.params.get(&80) {
+                    let item = *item;
+                    let value = self.item(item) + get_number(obj, 77).round() as i32;
+                    self.set_item(item, value, i, depth)?;
+                }
+            }
+            // instant count
+            1811 => {
+                if let Some(ObjParam::Item(item)) = obj.params.get(&80) {
+                    let value = self.item(*item);
+                    let num = get_number(obj, 77).round() as i32;
+                    let result = match get_number(obj, 88) as u8 {
+                        1 => value > num,
+                        2 => value < num,
+                        _ => value == num,
+                    };
+                    if result {
+                        self.activate_target(i, depth)?;
+                    }
+                }
+            }
+            // count, collision, touch, on death
+            1611 | 1815 | 1595 | 1812 => {
+                if !self.armed.contains(&i) {
+                    self.armed.push(i);
+                }
+            }
+            // stop
+            1616 => {
+                if let Some(target) = get_group(obj, 51) {
+                    // spawn triggers in the group stop waiting
+                    let objects = &self.objects;
+                    self.queue.retain(|_, p| match p {
+                        Pending::Activate(_, Some(source)) => {
+                            !obj_groups(&objects[*source]).contains(&target)
+                        }
+                        _ => true,
+                    });
+                }
+                self.log.push((self.time, Effect::Output(i)));
+            }
+            _ => self.log.push((self.time, Effect::Output(i))),
+        }
+        Ok(())
+    }
+
+    // the armed triggers with the given object id that the input sets off
+    fn fire_armed(&mut self, id: u16, matches: &dyn Fn(&GdObj) -> bool) -> Result<(), String> {
+        for i in self.armed.clone() {
+            let obj = &self.objects[i];
+            if obj_id(obj) == id && matches(obj) && self.enabled(i) {
+                self.activate_target(i, 0)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn input(&mut self, input: Input) -> Result<(), String> {
+        match input {
+            Input::Touch => {
+                // touch triggered objects
+                for i in 0..self.objects.len() {
+                    let obj = &self.objects[i];
+                    if obj.mode == ObjectMode::Trigger && get_bool(obj, 11) && self.enabled(i) {
+                        self.run_trigger(i, 0)?;
+                    }
+                }
+                self.fire_armed(1595, &|_| true)
+            }
+            Input::Collision(a, b) | Input::CollisionEnd(a, b) => {
+                let exit = matches!(input, Input::CollisionEnd(_, _));
+                self.fire_armed(1815, &|obj| {
+                    let blocks = (obj.params.get(&80), obj.params.get(&95));
+                    let pair = (Some(&ObjParam::Block(a)), Some(&ObjParam::Block(b)));
+                    let swapped = (Some(&ObjParam::Block(b)), Some(&ObjParam::Block(a)));
+                    (blocks == pair || blocks == swapped) && get_bool(obj, 93) == exit
+                })
+            }
+            Input::Death => self.fire_armed(1812, &|_| true),
+            Input::Spawn(group) => self.activate_group(group, 0),
+        }
+    }
+}
+
+// something a timeline checks at a certain time
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expectation {
+    Item(Item, i32),
+    Group(Group, bool),
+}
+
+pub struct Timeline {
+    pub inputs: Vec<(u64, Input)>,
+    // (time in milliseconds, what should be true, line in the file)
+    pub expectations: Vec<(u64, Expectation, usize)>,
+}
+
+fn parse_specific<T>(val: Option<&str>, suffix: char, make: fn(Id) -> T) -> Option<T> {
+    let val = val?;
+    val.trim_end_matches(suffix)
+        .parse::<u16>()
+        .ok()
+        .map(|n| make(Id::Specific(n)))
+}
+
+// one input or expectation per line, starting with the time in seconds:
+//     0.5 touch
+//     1 collide 1b 2b
+//     1.5 collide-end 1b 2b
+//     2 death
+//     0 spawn 5g
+//     3 expect 4i = 10
+//     3 expect 5g off
+pub fn parse_timeline(text: &str) -> Result<Timeline, String> {
+    let mut timeline = Timeline {
+        inputs: Vec::new(),
+        expectations: Vec::new(),
+    };
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = || format!("Invalid timeline line {}: {}", i + 1, line);
+        let block = |v| parse_specific(v, 'b', |id| Block { id });
+        let group = |v| parse_specific(v, 'g', |id| Group { id });
+
+        let mut parts = line.split_whitespace();
+        let time = match parts.next().map(|t| t.parse::<f64>()) {
+            Some(Ok(t)) if t >= 0.0 => (t * 1000.0).round() as u64,
+            _ => return Err(err()),
+        };
+        let input = match parts.next() {
+            Some("touch") => Input::Touch,
+            Some("death") => Input::Death,
+            Some("spawn") => Input::Spawn(group(parts.next()).ok_or_else(err)?),
+            Some(c) if c == "collide" || c == "collide-end" => {
+                let a = block(parts.next()).ok_or_else(err)?;
+                let b = block(parts.next()).ok_or_else(err)?;
+                if c == "collide" {
+                    Input::Collision(a, b)
+                } else {
+                    Input::CollisionEnd(a, b)
+                }
+            }
+            Some("expect") => {
+                let id = parts.next().ok_or_else(err)?;
+                let expectation = if id.ends_with('i') {
+                    let item = parse_specific(Some(id), 'i', |id| Item { id }).ok_or_else(err)?;
+                    match (parts.next(), parts.next().map(|v| v.parse::<i32>())) {
+                        (Some("="), Some(Ok(v))) => Expectation::Item(item, v),
+                        _ => return Err(err()),
+                    }
+                } else {
+                    let g = group(Some(id)).ok_or_else(err)?;
+                    match parts.next() {
+                        Some("on") => Expectation::Group(g, true),
+                        Some("off") => Expectation::Group(g, false),
+                        _ => return Err(err()),
+                    }
+                };
+                timeline.expectations.push((time, expectation, i + 1));
+                continue;
+            }
+            _ => return Err(err()),
+        };
+        timeline.inputs.push((time, input));
+    }
+    Ok(timeline)
+}
+
+// runs a level with a timeline, and returns the expectations that weren't met
+pub fn check_timeline(sim: &mut Simulation, timeline: &Timeline) -> Result<Vec<String>, String> {
+    let mut expectations = timeline.expectations.clone();
+    expectations.sort_by_key(|(time, _, line)| (*time, *line));
+
+    let mut failed = Vec::new();
+    for (time, expectation, line) in expectations {
+        sim.run_until(time)?;
+        match expectation {
+            Expectation::Item(item, value) => {
+                let actual = sim.item(item);
+                if actual != value {
+                    failed.push(format!(
+                        "line {}: expected {}i to be {} at {}s, but it was {}",
+                        line,
+                        ObjParam::Item(item),
+                        value,
+                        time as f64 / 1000.0,
+                        actual
+                    ));
+                }
+            }
+            Expectation::Group(group, on) => {
+                if sim.is_on(group) != on {
+                    failed.push(format!(
+                        "line {}: expected {}g to be toggled {} at {}s",
+                        line,
+                        ObjParam::Group(group),
+                        if on { "on" } else { "off" },
+                        time as f64 / 1000.0
+                    ));
+                }
+            }
+        }
+    }
+    Ok(failed)
+}
+
+// inputs to run a level with, when checking that two versions of it do the same thing
+pub struct Trace {
+    pub name: String,
+    pub inputs: Vec<(u64, Input)>,
+}
+
+// traces that set off every touch, collision and death trigger in the objects
+pub fn default_traces(objects: &[GdObj]) -> Vec<Trace> {
+    let mut traces = vec![Trace {
+        name: "no input".to_string(),
+        inputs: Vec::new(),
+    }];
+    let ids = objects.iter().map(obj_id).collect::<HashSet<u16>>();
+    if ids.contains(&1595) {
+        traces.push(Trace {
+            name: "touch at 0.5s, 1s and 1.5s".to_string(),
+            inputs: vec![
+                (500, Input::Touch),
+                (1000, Input::Touch),
+                (1500, Input::Touch),
+            ],
+        });
+    }
+    if ids.contains(&1812) {
+        traces.push(Trace {
+            name: "death at 0.5s".to_string(),
+            inputs: vec![(500, Input::Death)],
+        });
+    }
+    let mut pairs = Vec::new();
+    for obj in objects.iter().filter(|o| obj_id(o) == 1815) {
+        if let (Some(ObjParam::Block(a)), Some(ObjParam::Block(b))) =
+            (obj.params.get(&80), obj.params.get(&95))
+        {
+            if !pairs.contains(&(*a, *b)) {
+                pairs.push((*a, *b));
+            }
+        }
+    }
+    for (a, b) in pairs {
+        traces.push(Trace {
+            name: format!(
+                "{} and {} colliding from 0.5s to 1s",
+                show_id(a.id, 'b'),
+                show_id(b.id, 'b')
+            ),
+            inputs: vec![
+                (500, Input::Collision(a, b)),
+                (1000, Input::CollisionEnd(a, b)),
+            ],
+        });
+    }
+    traces
+}
+
+fn show_id(id: Id, suffix: char) -> String {
+    match id {
+        Id::Specific(n) => format!("{}{}", n, suffix),
+        Id::Arbitrary(n) => format!("?{}{}", suffix, n),
+    }
+}
+
+fn show_param(param: &ObjParam) -> String {
+    match param {
+        ObjParam::Group(g) => show_id(g.id, 'g'),
+        ObjParam::Color(c) => show_id(c.id, 'c'),
+        ObjParam::Block(b) => show_id(b.id, 'b'),
+        ObjParam::Item(i) => show_id(i.id, 'i'),
+        ObjParam::GroupList(l) => l
+            .iter()
+            .map(|g| show_id(g.id, 'g'))
+            .collect::<Vec<String>>()
+            .join("."),
+        a => a.to_string(),
+    }
+}
+
+// every key of an object, for showing which triggers were involved
+pub fn describe_obj(obj: &GdObj) -> String {
+    let mut params = obj.params.iter().collect::<Vec<(&u16, &ObjParam)>>();
+    params.sort_by_key(|(k, _)| **k);
+    params
+        .iter()
+        .map(|(k, v)| format!("{}: {}", k, show_param(v)))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// keys that only say where a trigger is and how it's activated,
+// which the optimizer is allowed to change
+const LAYOUT_KEYS: &[u16] = &[2, 3, 57, 62, 87];
+
+// what can be seen of a simulation: (time, what happened, the trigger that did it).
+// groups above `new_groups_from` are made by the optimizer, so toggling them is left out
+fn observable(sim: &Simulation, new_groups_from: u16) -> Vec<(u64, String, usize)> {
+    let mut out = sim
+        .log
+        .iter()
+        .filter_map(|(time, effect)| {
+            let (text, trigger) = match effect {
+                Effect::Output(i) => {
+                    let mut params = sim.objects[*i]
+                        .params
+                        .iter()
+                        .filter(|(k, _)| !LAYOUT_KEYS.contains(k))
+                        .collect::<Vec<(&u16, &ObjParam)>>();
+                    params.sort_by_key(|(k, _)| **k);
+                    let params = params
+                        .iter()
+                        .map(|(k, v)| format!("{}: {}", k, show_param(v)))
+                        .collect::<Vec<String>>()
+                        .join(", ");
+                    (format!("trigger ran ({})", params), *i)
+                }
+                Effect::ItemChanged(item, value, i) => {
+                    (format!("{} set to {}", show_id(item.id, 'i'), value), *i)
+                }
+                Effect::Toggled(group, on, i) => {
+                    if let Id::Arbitrary(n) = group.id {
+                        if n > new_groups_from {
+                            return None;
+                        }
+                    }
+                    (
+                        format!(
+                            "{} toggled {}",
+                            show_id(group.id, 'g'),
+                            if *on { "on" } else { "off" }
+                        ),
+                        *i,
+                    )
+                }
+            };
+            Some((*time, text, trigger))
+        })
+        .collect::<Vec<(u64, String, usize)>>();
+    // things that happen at the same time can happen in any order
+    out.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+    out
+}
+
+// runs every trace on both versions of the level, and describes
+// the first difference in what they do, if there is one
+pub fn compare_levels(
+    reference: Vec<GdObj>,
+    optimized: Vec<GdObj>,
+    new_groups_from: u16,
+    traces: &[Trace],
+) -> Result<Option<String>, String> {
+    for trace in traces {
+        let until = trace.inputs.iter().map(|(t, _)| *t).max().unwrap_or(0) + 10000;
+
+        let mut sims = Vec::new();
+        for objects in [&reference, &optimized].iter() {
+            let mut sim = Simulation::new((*objects).clone(), &trace.inputs);
+            sim.run_until(until)?;
+            sims.push(sim);
+        }
+        let expected = observable(&sims[0], new_groups_from);
+        let found = observable(&sims[1], new_groups_from);
+
+        for i in 0..expected.len().max(found.len()) {
+            let (a, b) = (expected.get(i), found.get(i));
+            if a.map(|x| (x.0, &x.1)) == b.map(|x| (x.0, &x.1)) {
+                continue;
+            }
+            let show = |event: Option<&(u64, String, usize)>, sim: &Simulation| match event {
+                Some((time, text, trigger)) => format!(
+                    "at {:.3}s: {}\n    from trigger {}",
+                    *time as f64 / 1000.0,
+                    text,
+                    describe_obj(&sim.objects[*trigger])
+                ),
+                None => "nothing".to_string(),
+            };
+            return Ok(Some(format!(
+                "The optimized level does something else than the unoptimized one (with {}):\n  expected {}\n  found {}",
+                trace.name,
+                show(a, &sims[0]),
+                show(b, &sims[1]),
+            )));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{block, group, item, obj, specific};
+
+    // a pickup trigger adding `amount` to an item
+    fn pickup(id: u16, amount: f64, mut params: Vec<(u16, ObjParam)>) -> GdObj {
+        params.extend(vec![
+            (1, ObjParam::Number(1817.0)),
+            (77, ObjParam::Number(amount)),
+            (80, specific(item, id)),
+        ]);
+        obj(ObjectMode::Trigger, params)
+    }
+
+    #[test]
+    fn timelines() {
+        let timeline = parse_timeline(
+            "# comment\n0.5 touch\n1 collide 1b 2b\n1.5 collide-end 1b 2b\n2 death\n0 spawn 5g\n3 expect 4i = 10\n3 expect 5g off\n",
+        )
+        .unwrap();
+        assert_eq!(timeline.inputs.len(), 5);
+        assert_eq!(timeline.inputs[0], (500, Input::Touch));
+        assert_eq!(
+            timeline.inputs[4],
+            (
+                0,
+                Input::Spawn(Group {
+                    id: Id::Specific(5)
+                })
+            )
+        );
+        assert_eq!(
+            timeline.expectations[0],
+            (
+                3000,
+                Expectation::Item(
+                    Item {
+                        id: Id::Specific(4)
+                    },
+                    10
+                ),
+                7
+            )
+        );
+        assert_eq!(
+            timeline.expectations[1],
+            (
+                3000,
+                Expectation::Group(
+                    Group {
+                        id: Id::Specific(5)
+                    },
+                    false
+                ),
+                8
+            )
+        );
+
+        for line in [
+            "touch",
+            "-1 touch",
+            "1 jump",
+            "1 spawn",
+            "1 expect 4i 10",
+            "1 expect 5g maybe",
+        ]
+        .iter()
+        {
+            assert!(parse_timeline(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn spawn_and_player_triggers() {
+        let objects = vec![
+            // the player gets here after 2 seconds
+            pickup(1, 1.0, vec![(2, ObjParam::Number(PLAYER_SPEED * 2.0))]),
+            // only runs when group 5 is spawned, no matter where it is
+            pickup(
+                2,
+                5.0,
+                vec![
+                    (2, ObjParam::Number(0.0)),
+                    (57, specific(group, 5)),
+                    (62, ObjParam::Bool(true)),
+                ],
+            ),
+            // spawns group 5 half a second after group 6
+            obj(
+                ObjectMode::Trigger,
+                vec![
+                    (1, ObjParam::Number(1268.0)),
+                    (51, specific(group, 5)),
+                    (57, specific(group, 6)),
+                    (62, ObjParam::Bool(true)),
+                    (63, ObjParam::Number(0.5)),
+                ],
+            ),
+        ];
+        let timeline = parse_timeline(
+            "1 spawn 5g\n1.5 spawn 6g\n0.5 expect 1i = 0\n0.5 expect 2i = 0\n1 expect 2i = 5\n1.9 expect 1i = 0\n1.9 expect 2i = 5\n2 expect 1i = 1\n2 expect 2i = 10\n",
+        )
+        .unwrap();
+        let mut sim = Simulation::new(objects.clone(), &timeline.inputs);
+        assert_eq!(
+            check_timeline(&mut sim, &timeline).unwrap(),
+            Vec::<String>::new()
+        );
+
+        let timeline = parse_timeline("1 expect 1i = 1\n").unwrap();
+        let mut sim = Simulation::new(objects, &timeline.inputs);
+        let failed = check_timeline(&mut sim, &timeline).unwrap();
+        assert_eq!(
+            failed,
+            vec!["line 1: expected 1i to be 1 at 1s, but it was 0".to_string()]
+        );
+    }
+
+    #[test]
+    fn comparing_levels() {
+        // group 5 is spawned from the player's path, after a delay
+        let spawn = |delay: f64| {
+            obj(
+                ObjectMode::Trigger,
+                vec![
+                    (1, ObjParam::Number(1268.0)),
+                    (2, ObjParam::Number(15.0)),
+                    (51, specific(group, 5)),
+                    (63, ObjParam::Number(delay)),
+                ],
+            )
+        };
+        let level = |delay| {
+            vec![
+                spawn(delay),
+                pickup(
+                    1,
+                    1.0,
+                    vec![(57, specific(group, 5)), (62, ObjParam::Bool(true))],
+                ),
+            ]
+        };
+        let traces = default_traces(&level(0.5));
+        assert_eq!(traces.len(), 1);
+
+        // moving a trigger around doesn't change what it does
+        let mut moved = level(0.5);
+        moved[1].params.insert(2, ObjParam::Number(300.0));
+        assert_eq!(compare_levels(level(0.5), moved, 0, &traces).unwrap(), None);
+
+        let divergence = compare_levels(level(0.5), level(0.2), 0, &traces)
+            .unwrap()
+            .unwrap();
+        assert!(divergence.contains("with no input"), "{}", divergence);
+        assert!(
+            divergence.contains("expected at 0.548s: 1i set to 1"),
+            "{}",
+            divergence
+        );
+        assert!(
+            divergence.contains("found at 0.248s: 1i set to 1"),
+            "{}",
+            divergence
+        );
+
+        // every touch, death and collision trigger gets a trace
+        let mut armed = level(0.5);
+        armed.push(obj(
+            ObjectMode::Trigger,
+            vec![(1, ObjParam::Number(1595.0)), (51, specific(group, 5))],
+        ));
+        armed.push(obj(
+            ObjectMode::Trigger,
+            vec![(1, ObjParam::Number(1812.0)), (51, specific(group, 5))],
+        ));
+        armed.push(obj(
+            ObjectMode::Trigger,
+            vec![
+                (1, ObjParam::Number(1815.0)),
+                (51, specific(group, 5)),
+                (80, specific(block, 1)),
+                (95, specific(block, 2)),
+            ],
+        ));
+        assert_eq!(default_traces(&armed).len(), 4);
+    }
+}
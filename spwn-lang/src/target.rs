@@ -0,0 +1,552 @@
+// settings for the version of geometry dash the level is made for
+use crate::ast::ObjectMode;
+use crate::levelstring::{GdObj, ObjParam, GD22_TRIGGER_IDS};
+use std::collections::HashMap;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TriggerRole {
+    // Spawn triggers have their own catagory
+    // because they can be combined by adding their delays
+    Spawn,
+
+    // Triggers like move and rotate, which have some output in the level
+    // and therefore cannot be optimized away
+    Output,
+
+    // Triggers that send a signal, but don't cause any side effects
+    Func,
+}
+
+// what the optimizer needs to know about a trigger
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggerInfo {
+    pub role: TriggerRole,
+    // keys that hold groups the trigger activates or changes
+    pub groups: Vec<u16>,
+    pub item_reads: Vec<u16>,
+    pub item_writes: Vec<u16>,
+    pub block_reads: Vec<u16>,
+}
+
+pub type TriggerTable = HashMap<u16, TriggerInfo>;
+
+// one trigger per line: [object id] [role] [key list]=[keys]...
+// triggers that aren't in the table are treated as output triggers
+const GD21_TRIGGERS: &str = "
+# move, pulse, alpha, toggle, rotate, follow, shake, animate, stop, follow player y
+901 output groups=51,71
+1006 output groups=51
+1007 output groups=51
+1049 output groups=51
+1346 output groups=51,71
+1347 output groups=51,71
+1520 output
+1585 output groups=51
+1616 output groups=51
+1814 output groups=51
+
+1268 spawn groups=51
+
+# touch, count, instant count, collision, on death
+1595 func groups=51
+1611 func groups=51 item_reads=80
+1811 func groups=51 item_reads=80
+1815 func groups=51 block_reads=80,95
+1812 func groups=51
+
+# pickup
+1817 output item_writes=80
+";
+
+const GD22_TRIGGERS: &str = "
+# event, time event
+3604 func groups=51
+3615 func groups=51
+";
+
+// what kind of value an object property holds
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PropKind {
+    Number,
+    Bool,
+    Text,
+    Group,
+    // one group, or a list of them
+    Groups,
+    Color,
+    Item,
+    Block,
+    Epsilon,
+    // properties whose values aren't checked
+    Any,
+}
+
+// object key -> the kinds of values it can have
+pub type PropSchema = HashMap<u16, Vec<PropKind>>;
+
+// one property per line: [key or start..end] [kinds separated by |]
+// from obj_props in the standard library (constants.spwn). the keys it
+// doesn't have are used by the editor for other objects, so any value goes
+const GD21_PROPS: &str = "
+1..4 number
+4 bool
+5 bool
+6..11 number
+11 bool
+# secret coin id
+12 number
+13 bool
+# tint ground
+14 bool
+15..18 bool
+18 any
+# the old color channel
+19 number
+20 number
+21..24 color
+24..26 number
+26..28 any
+28..31 number
+31 text
+32 number
+# the old single group
+33 any
+34 bool
+35 number
+36 bool
+37..41 any
+41 bool
+42 bool
+43 text
+44 text
+45..48 number
+48 bool
+49 text
+50 color
+51 color|group
+52 number
+53 any
+54 number
+55 any
+56 bool
+57 groups
+58..61 bool
+61 number
+62 bool
+63 number|epsilon
+64..68 bool
+68 number
+69 number
+70 bool
+71 group
+72 number
+73 number
+74 any
+75..80 number
+80 item|block
+81 bool
+82 number
+83 any
+84 number
+85 number
+86 bool
+87 bool
+88 number
+89 bool
+90..93 number
+93 bool
+94 bool
+95 block
+96 bool
+97 number
+98 bool
+# multi activate (orbs and pads)
+99 bool
+100 bool
+101 number
+102..105 bool
+105 number
+106 bool
+107 number
+108 number
+";
+
+// 2.2 added a lot of properties, which aren't typed here yet
+const GD22_PROPS: &str = "
+109..1000 any
+";
+
+pub fn parse_prop_schema(schema: &str) -> Result<PropSchema, String> {
+    let mut out = PropSchema::new();
+    for (i, line) in schema.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |msg: &str| format!("{} in property schema line {}: {}", msg, i + 1, line);
+
+        let mut parts = line.split_whitespace();
+        let keys = match parts.next() {
+            Some(keys) => keys,
+            None => return Err(err("Expected a key")),
+        };
+        let (start, end) = match keys.find("..") {
+            Some(i) => (keys[..i].parse::<u16>(), keys[i + 2..].parse::<u16>()),
+            None => (keys.parse::<u16>(), keys.parse::<u16>().map(|k| k + 1)),
+        };
+        let (start, end) = match (start, end) {
+            (Ok(start), Ok(end)) if start < end => (start, end),
+            _ => return Err(err("Invalid key")),
+        };
+
+        let mut kinds = Vec::new();
+        for kind in parts.next().unwrap_or("").split('|') {
+            kinds.push(match kind {
+                "number" => PropKind::Number,
+                "bool" => PropKind::Bool,
+                "text" => PropKind::Text,
+                "group" => PropKind::Group,
+                "groups" => PropKind::Groups,
+                "color" => PropKind::Color,
+                "item" => PropKind::Item,
+                "block" => PropKind::Block,
+                "epsilon" => PropKind::Epsilon,
+                "any" => PropKind::Any,
+                _ => return Err(err("Unknown property kind")),
+            });
+        }
+        for key in start..end {
+            out.insert(key, kinds.clone());
+        }
+    }
+    Ok(out)
+}
+
+fn param_fits(param: &ObjParam, kinds: &[PropKind]) -> bool {
+    kinds.iter().any(|kind| match (kind, param) {
+        (PropKind::Any, _) => true,
+        // gd saves bools as numbers, so either one works
+        (PropKind::Number, ObjParam::Number(_))
+        | (PropKind::Number, ObjParam::Bool(_))
+        | (PropKind::Bool, ObjParam::Bool(_))
+        | (PropKind::Bool, ObjParam::Number(_)) => true,
+        (PropKind::Text, ObjParam::Text(_)) => true,
+        (PropKind::Group, ObjParam::Group(_)) => true,
+        (PropKind::Groups, ObjParam::Group(_)) | (PropKind::Groups, ObjParam::GroupList(_)) => true,
+        (PropKind::Color, ObjParam::Color(_)) => true,
+        (PropKind::Item, ObjParam::Item(_)) => true,
+        (PropKind::Block, ObjParam::Block(_)) => true,
+        (PropKind::Epsilon, ObjParam::Epsilon) => true,
+        _ => false,
+    })
+}
+
+pub fn parse_role(role: &str) -> Result<TriggerRole, String> {
+    match role {
+        "spawn" => Ok(TriggerRole::Spawn),
+        "func" => Ok(TriggerRole::Func),
+        "output" => Ok(TriggerRole::Output),
+        a => Err(format!(
+            "Unknown trigger role {} (expected spawn, func or output)",
+            a
+        )),
+    }
+}
+
+pub fn parse_trigger_table(table: &str) -> Result<TriggerTable, String> {
+    let mut out = TriggerTable::new();
+    for (i, line) in table.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |msg: &str| format!("{} in trigger table line {}: {}", msg, i + 1, line);
+
+        let mut parts = line.split_whitespace();
+        let obj_id = match parts.next().map(|id| id.parse::<u16>()) {
+            Some(Ok(id)) => id,
+            _ => return Err(err("Invalid object id")),
+        };
+        let role = match parts.next() {
+            Some(role) => parse_role(role).map_err(|e| err(&e))?,
+            None => return Err(err("Expected a role")),
+        };
+        let mut info = TriggerInfo {
+            role,
+            groups: Vec::new(),
+            item_reads: Vec::new(),
+            item_writes: Vec::new(),
+            block_reads: Vec::new(),
+        };
+
+        for part in parts {
+            let mut split = part.splitn(2, '=');
+            let list = match split.next() {
+                Some("groups") => &mut info.groups,
+                Some("item_reads") => &mut info.item_reads,
+                Some("item_writes") => &mut info.item_writes,
+                Some("block_reads") => &mut info.block_reads,
+                _ => {
+                    return Err(err(
+                        "Expected groups, item_reads, item_writes or block_reads",
+                    ))
+                }
+            };
+            for key in split
+                .next()
+                .unwrap_or("")
+                .split(',')
+                .filter(|k| !k.is_empty())
+            {
+                match key.parse::<u16>() {
+                    Ok(key) => list.push(key),
+                    Err(_) => return Err(err("Invalid key")),
+                }
+            }
+        }
+        out.insert(obj_id, info);
+    }
+    Ok(out)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub name: &'static str,
+    // the highest id of each class (groups, colors, block IDs, item IDs)
+    // that can be given to an arbitrary id
+    pub id_limits: [u16; 4],
+    // roles and read/written ids of triggers, for the optimizer
+    pub triggers: TriggerTable,
+    // whether the triggers added in 2.2 exist
+    pub gd22_triggers: bool,
+    // the properties objects can have
+    pub props: PropSchema,
+}
+
+pub const TARGET_NAMES: &[&str] = &["gd2.1", "gd2.2"];
+
+impl Default for Target {
+    fn default() -> Self {
+        Target::from_name("gd2.1").unwrap()
+    }
+}
+
+impl Target {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        let mut triggers = parse_trigger_table(GD21_TRIGGERS)?;
+        let mut props = parse_prop_schema(GD21_PROPS)?;
+
+        match name {
+            "gd2.1" => Ok(Target {
+                name: "gd2.1",
+                id_limits: [999, 999, 999, 999],
+                triggers,
+                gd22_triggers: false,
+                props,
+            }),
+            "gd2.2" => {
+                triggers.extend(parse_trigger_table(GD22_TRIGGERS)?);
+                props.extend(parse_prop_schema(GD22_PROPS)?);
+                Ok(Target {
+                    name: "gd2.2",
+                    // colors above 999 are still the special channels (BG, ground, etc.)
+                    id_limits: [9999, 999, 9999, 9999],
+                    triggers,
+                    gd22_triggers: true,
+                    props,
+                })
+            }
+            a => Err(format!(
+                "Unknown target {} (expected one of: {})",
+                a,
+                TARGET_NAMES.join(", ")
+            )),
+        }
+    }
+
+    pub fn get_role(&self, obj_id: u16, hd: bool) -> TriggerRole {
+        match self.triggers.get(&obj_id).map(|t| t.role) {
+            // high detail spawn triggers don't always run,
+            // so they can't be merged with others
+            Some(TriggerRole::Spawn) if hd => TriggerRole::Func,
+            Some(role) => role,
+            None => TriggerRole::Output,
+        }
+    }
+
+    // warnings for objects that don't exist in this version
+    pub fn check_objects(&self, objects: &[GdObj]) -> Vec<String> {
+        let mut unsupported = objects
+            .iter()
+            .filter(|o| o.mode == ObjectMode::Trigger)
+            .filter_map(|o| match o.params.get(&1) {
+                Some(ObjParam::Number(n)) => Some(*n as u16),
+                _ => None,
+            })
+            .filter(|id| !self.gd22_triggers && GD22_TRIGGER_IDS.contains(id))
+            .collect::<Vec<u16>>();
+        unsupported.sort_unstable();
+        unsupported.dedup();
+
+        let mut out = unsupported
+            .iter()
+            .map(|id| format!("Trigger {} does not exist in {}", id, self.name))
+            .collect::<Vec<String>>();
+
+        let mut unknown = Vec::new();
+        let mut mismatched = Vec::new();
+        for obj in objects {
+            for (key, param) in &obj.params {
+                match self.props.get(key) {
+                    None => unknown.push(*key),
+                    Some(kinds) if !param_fits(param, kinds) => mismatched.push(*key),
+                    _ => (),
+                }
+            }
+        }
+        for (keys, problem) in [
+            (&mut unknown, "does not exist in"),
+            (&mut mismatched, "has a value of the wrong type for"),
+        ]
+        .iter_mut()
+        {
+            keys.sort_unstable();
+            keys.dedup();
+            out.extend(
+                keys.iter()
+                    .map(|key| format!("Object property {} {} {}", key, problem, self.name)),
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levelstring::apply_fn_ids;
+    use crate::test_util::*;
+
+    #[test]
+    fn id_limits() {
+        let gd21 = Target::from_name("gd2.1").unwrap();
+        let gd22 = Target::from_name("gd2.2").unwrap();
+        assert_eq!(gd21.id_limits, [999, 999, 999, 999]);
+        assert_eq!(gd22.id_limits, [9999, 999, 9999, 9999]);
+        assert_eq!(Target::default(), gd21);
+        assert!(Target::from_name("gd1.9").is_err());
+    }
+
+    #[test]
+    fn object_properties() {
+        let gd21 = Target::default();
+        let gd22 = Target::from_name("gd2.2").unwrap();
+        let trigger = |params: Vec<(u16, ObjParam)>| obj(ObjectMode::Trigger, params);
+
+        let fine = vec![trigger(vec![
+            (1, ObjParam::Number(901.0)),
+            (51, specific(group, 1)),
+            (57, specific(group, 1)),
+            (62, ObjParam::Bool(true)),
+            (87, ObjParam::Number(1.0)),
+        ])];
+        assert!(gd21.check_objects(&fine).is_empty());
+
+        let new_key = vec![trigger(vec![
+            (1, ObjParam::Number(901.0)),
+            (120, ObjParam::Number(1.0)),
+        ])];
+        assert_eq!(
+            gd21.check_objects(&new_key),
+            vec!["Object property 120 does not exist in gd2.1".to_string()]
+        );
+        assert!(gd22.check_objects(&new_key).is_empty());
+
+        let wrong_type = vec![trigger(vec![
+            (1, ObjParam::Number(901.0)),
+            (10, specific(group, 1)),
+        ])];
+        assert_eq!(
+            gd21.check_objects(&wrong_type),
+            vec!["Object property 10 has a value of the wrong type for gd2.1".to_string()]
+        );
+
+        let event = vec![trigger(vec![(1, ObjParam::Number(3604.0))])];
+        assert_eq!(
+            gd21.check_objects(&event),
+            vec!["Trigger 3604 does not exist in gd2.1".to_string()]
+        );
+        assert!(gd22.check_objects(&event).is_empty());
+
+        // every key up to 108 is a 2.1 property
+        for key in 1..=108 {
+            assert!(gd21.props.contains_key(&key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn normal_scripts_have_no_warnings() {
+        let globals = compile_std(
+            "
+            extract obj_props
+            g = ?g
+            c = ?c
+            $.add(obj {
+                OBJ_ID: 1,
+                X: 15,
+                Y: 15,
+                GROUPS: g,
+                COLOR: c,
+            })
+            10g.move(10, 0, 0.5)
+            c.set(255, 0, 0, 0.5)
+            c.pulse(0, 255, 0, fade_out = 0.5)
+            i = counter(5)
+            i += 2
+            -> (){
+                wait(1)
+                g.toggle_off()
+            }()
+            ",
+        )
+        .unwrap();
+        let mut objects = apply_fn_ids(&globals.func_ids, &Default::default(), &[]);
+        objects.extend(globals.objects);
+        assert!(objects.len() > 5);
+        for target in TARGET_NAMES {
+            assert_eq!(
+                Target::from_name(target).unwrap().check_objects(&objects),
+                Vec::<String>::new()
+            );
+        }
+    }
+
+    #[test]
+    fn schemas_and_trigger_roles() {
+        let schema = parse_prop_schema("1..4 number\n2 bool|text").unwrap();
+        assert_eq!(schema[&1], vec![PropKind::Number]);
+        assert_eq!(schema[&2], vec![PropKind::Bool, PropKind::Text]);
+        assert_eq!(schema[&3], vec![PropKind::Number]);
+        assert!(!schema.contains_key(&4));
+        assert!(parse_prop_schema("5 float").is_err());
+        assert!(parse_prop_schema("5..5 number").is_err());
+
+        let gd21 = Target::default();
+        assert_eq!(gd21.get_role(1268, false), TriggerRole::Spawn);
+        assert_eq!(gd21.get_role(1268, true), TriggerRole::Func);
+        assert_eq!(gd21.get_role(1611, false), TriggerRole::Func);
+        assert_eq!(gd21.get_role(901, false), TriggerRole::Output);
+        assert_eq!(gd21.get_role(3604, false), TriggerRole::Output);
+        let gd22 = Target::from_name("gd2.2").unwrap();
+        assert_eq!(gd22.get_role(3604, false), TriggerRole::Func);
+
+        let table =
+            parse_trigger_table("# comment\n1268 spawn groups=51\n1817 output item_writes=80")
+                .unwrap();
+        assert_eq!(table[&1268].role, TriggerRole::Spawn);
+        assert_eq!(table[&1268].groups, vec![51]);
+        assert_eq!(table[&1817].item_writes, vec![80]);
+        assert!(parse_trigger_table("1268 teleport").is_err());
+        assert!(parse_trigger_table("1268 spawn targets=51").is_err());
+    }
+}
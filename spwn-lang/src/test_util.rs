@@ -0,0 +1,91 @@
+// helpers shared by the tests of different modules
+
+use crate::ast::ObjectMode;
+use crate::builtin::{Block, Group, Id, Item};
+use crate::compiler::compile_spwn;
+use crate::compiler_types::FunctionId;
+use crate::globals::Globals;
+use crate::levelstring::{GdObj, ObjParam};
+use crate::parser::parse_spwn;
+use std::path::PathBuf;
+
+// an object with only the given parameters
+pub fn obj(mode: ObjectMode, params: Vec<(u16, ObjParam)>) -> GdObj {
+    GdObj {
+        func_id: 0,
+        params: params.into_iter().collect(),
+        mode,
+        unique_id: 0,
+        sync_group: 0,
+        sync_part: 0,
+        source: None,
+    }
+}
+
+// a trigger with only the given parameters, as it is stored in a function id
+pub fn trigger(params: Vec<(u16, ObjParam)>) -> (GdObj, usize) {
+    (obj(ObjectMode::Trigger, params), 0)
+}
+
+// a top-level function id with the given triggers
+pub fn function(obj_list: Vec<(GdObj, usize)>) -> FunctionId {
+    FunctionId {
+        parent: None,
+        width: None,
+        name: None,
+        site: None,
+        obj_list,
+        file: None,
+    }
+}
+
+pub fn group(id: Id) -> ObjParam {
+    ObjParam::Group(Group { id })
+}
+
+pub fn block(id: Id) -> ObjParam {
+    ObjParam::Block(Block { id })
+}
+
+pub fn item(id: Id) -> ObjParam {
+    ObjParam::Item(Item { id })
+}
+
+pub fn specific(class: fn(Id) -> ObjParam, id: u16) -> ObjParam {
+    class(Id::Specific(id))
+}
+
+// compiles a script without the standard library, in a level with the given objects
+pub fn compile(code: &str, level_objects: Vec<GdObj>) -> Result<Globals, String> {
+    let code = match code.strip_prefix("#[") {
+        Some(rest) => format!("#[no_std, {}", rest),
+        None => format!("#[no_std]\n{}", code),
+    };
+    compile_code(code, Vec::new(), level_objects)
+}
+
+// compiles a script with the standard library, in an empty level
+pub fn compile_std(code: &str) -> Result<Globals, String> {
+    // the libraries folder is next to the manifest
+    let includes = vec![PathBuf::from(env!("CARGO_MANIFEST_DIR"))];
+    compile_code(code.to_string(), includes, Vec::new())
+}
+
+fn compile_code(
+    code: String,
+    includes: Vec<PathBuf>,
+    level_objects: Vec<GdObj>,
+) -> Result<Globals, String> {
+    // compiling needs more stack than test threads get in debug builds
+    std::thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(move || {
+            let path = PathBuf::from("test.spwn");
+            let (statements, notes) = parse_spwn(code, path.clone()).map_err(|e| e.to_string())?;
+            compile_spwn(statements, path, includes, notes, level_objects)
+                .map_err(|e| e.to_string())
+        })
+        .unwrap()
+        .join()
+        .unwrap()
+}
@@ -49,10 +49,16 @@ impl std::ops::IndexMut<usize> for ValStorage {
 }
 
 use std::collections::HashSet;
+impl Default for ValStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ValStorage {
     pub fn new() -> Self {
         ValStorage {
-            map: vec![
+            map: [
                 (
                     BUILTIN_STORAGE,
                     StoredValData {
@@ -80,7 +86,7 @@ impl ValStorage {
 
     pub fn set_mutability(&mut self, index: usize, mutable: bool) {
         if !mutable || !matches!(self[index], Value::Macro(_)) {
-            (*self.map.get_mut(&index).unwrap()).mutable = mutable;
+            self.map.get_mut(&index).unwrap().mutable = mutable;
         }
 
         match self[index].clone() {
@@ -105,13 +111,13 @@ impl ValStorage {
 
     pub fn increment_lifetimes(&mut self) {
         for (_, val) in self.map.iter_mut() {
-            (*val).lifetime += 1;
+            val.lifetime += 1;
         }
     }
 
     pub fn decrement_lifetimes(&mut self) {
         for (_, val) in self.map.iter_mut() {
-            (*val).lifetime -= 1;
+            val.lifetime -= 1;
         }
     }
 
@@ -134,16 +140,16 @@ impl ValStorage {
         amount: u16,
         already_done: &mut HashSet<usize>,
     ) {
-        if already_done.get(&index) == None {
+        if already_done.get(&index).is_none() {
             (*already_done).insert(index);
         } else {
             return;
         }
-        let val = &mut (*self
+        let val = &mut self
             .map
             .get_mut(&index)
-            .expect(&(index.to_string() + " index not found")))
-        .lifetime;
+            .unwrap_or_else(|| panic!("{}", (index.to_string() + " index not found")))
+            .lifetime;
 
         if *val < 10000 - amount {
             *val += amount;
@@ -188,7 +194,7 @@ pub fn store_value(
     let index = globals.val_id;
     let mutable = !matches!(val, Value::Macro(_));
 
-    (*globals).stored_values.map.insert(
+    globals.stored_values.map.insert(
         index,
         StoredValData {
             val,
@@ -197,7 +203,7 @@ pub fn store_value(
             lifetime,
         },
     );
-    (*globals).val_id += 1;
+    globals.val_id += 1;
     index
 }
 pub fn clone_and_get_value(
@@ -267,7 +273,7 @@ pub fn clone_value(
     //profit
     let new_index = globals.val_id;
 
-    (*globals).stored_values.map.insert(
+    globals.stored_values.map.insert(
         new_index,
         StoredValData {
             val: old_val,
@@ -276,7 +282,7 @@ pub fn clone_value(
             lifetime,
         },
     );
-    (*globals).val_id += 1;
+    globals.val_id += 1;
     new_index
 }
 
@@ -288,7 +294,7 @@ pub fn store_const_value(
 ) -> StoredValue {
     let index = globals.val_id;
 
-    (*globals).stored_values.map.insert(
+    globals.stored_values.map.insert(
         index,
         StoredValData {
             val,
@@ -297,7 +303,7 @@ pub fn store_const_value(
             lifetime,
         },
     );
-    (*globals).val_id += 1;
+    globals.val_id += 1;
     index
 }
 
@@ -310,7 +316,7 @@ pub fn store_val_m(
 ) -> StoredValue {
     let index = globals.val_id;
 
-    (*globals).stored_values.map.insert(
+    globals.stored_values.map.insert(
         index,
         StoredValData {
             val,
@@ -319,6 +325,6 @@ pub fn store_val_m(
             lifetime,
         },
     );
-    (*globals).val_id += 1;
+    globals.val_id += 1;
     index
 }